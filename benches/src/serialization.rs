@@ -2,8 +2,8 @@ mod mock_data;
 mod types;
 
 use bytes::BytesMut;
-use criterion::{Criterion, black_box, criterion_group, criterion_main};
-use imprint::{ImprintRecord, Merge, Project, Read, Write};
+use criterion::{Criterion, Throughput, black_box, criterion_group, criterion_main};
+use imprint::{ImprintRecord, Merge, Predicate, Project, Read, Value, Write};
 use prost::Message;
 use types::{EnrichedOrder, Order, Product, SimpleProduct};
 
@@ -145,11 +145,89 @@ fn benchmark_project(c: &mut Criterion) {
     group.finish();
 }
 
+fn benchmark_size(c: &mut Criterion) {
+    // throughput is reported in encoded bytes, which puts the size of each encoding
+    // next to its serialization time
+    let mut group = c.benchmark_group("size");
+
+    for size in [1, 5, 10].iter() {
+        let product = mock_data::mock_product(*size);
+        let protobuf_size = product.encode_to_vec().len();
+        group.throughput(Throughput::Bytes(protobuf_size as u64));
+        group.bench_function(format!("protobuf_serialize_size_{}", size), |b| {
+            b.iter(|| black_box(product.encode_to_vec()))
+        });
+
+        let mut product_imprint = product.to_imprint().unwrap();
+        for compact in [false, true] {
+            product_imprint.set_compact_directory(compact);
+            let mut buf = BytesMut::new();
+            product_imprint.write(&mut buf).unwrap();
+            group.throughput(Throughput::Bytes(buf.len() as u64));
+
+            let name = if compact {
+                "imprint_compact"
+            } else {
+                "imprint"
+            };
+            group.bench_function(format!("{}_serialize_size_{}", name, size), |b| {
+                b.iter(|| {
+                    let mut buf = BytesMut::new();
+                    product_imprint.write(&mut buf).unwrap();
+                    black_box(buf);
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn benchmark_filter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filter");
+    let products: Vec<ImprintRecord> = (0..100)
+        .map(|_| mock_data::mock_product(5).to_imprint().unwrap())
+        .collect();
+
+    let predicate = Predicate::Gt(4, Value::Float64(100.0))
+        .and(Predicate::StartsWith(7, "A".into()))
+        .and(Predicate::Contains(8, "et".into()));
+    let compiled = predicate.compile().unwrap();
+
+    group.bench_function("imprint_filter_raw", |b| {
+        b.iter(|| {
+            let matches = products
+                .iter()
+                .filter(|product| compiled.matches(product).unwrap())
+                .count();
+            black_box(matches);
+        })
+    });
+
+    group.bench_function("imprint_filter_decoded", |b| {
+        b.iter(|| {
+            let matches = products
+                .iter()
+                .filter(|product| {
+                    matches!(product.get_value(4).unwrap(), Some(Value::Float64(price)) if price > 100.0)
+                        && matches!(product.get_value(7).unwrap(), Some(Value::String(brand)) if brand.starts_with('A'))
+                        && matches!(product.get_value(8).unwrap(), Some(Value::Array(tags)) if tags.contains(&"et".into()))
+                })
+                .count();
+            black_box(matches);
+        })
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     benchmark_serialize,
     benchmark_deserialize,
     benchmark_merge,
-    benchmark_project
+    benchmark_project,
+    benchmark_size,
+    benchmark_filter
 );
 criterion_main!(benches);
@@ -24,7 +24,7 @@ impl Product {
         writer.add_field(9, Value::Bool(self.is_active))?;
         writer.add_field(10, Value::String(self.sku.clone()))?;
 
-        Ok(writer.build()?)
+        writer.build()
     }
 }
 
@@ -44,6 +44,6 @@ impl Order {
             Value::Array(self.tags.iter().map(|t| Value::String(t.clone())).collect()),
         )?;
 
-        Ok(writer.build()?)
+        writer.build()
     }
 }
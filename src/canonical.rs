@@ -0,0 +1,265 @@
+use bytes::{BufMut, BytesMut};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::{
+    error::ImprintError,
+    serde::Write,
+    types::{ImprintRecord, Value},
+    varint,
+};
+
+/// Bits of the single NaN all NaNs are canonicalized to
+const CANONICAL_NAN_F32: u32 = 0x7fc0_0000;
+const CANONICAL_NAN_F64: u64 = 0x7ff8_0000_0000_0000;
+
+impl ImprintRecord {
+    /// A stable, seedless hash of the live fields of this record, so that equal
+    /// rows hash equal however they were written, merged or compressed.
+    ///
+    /// The hash covers the fieldspace ID and each field's ID, logical type and
+    /// value. It ignores the schema hash, zombie payload bytes, compression,
+    /// checksums and the directory encoding. Integers written as varints hash as
+    /// their fixed-width type, map entries hash in key order, all NaNs hash as
+    /// one NaN, `-0.0` hashes as `0.0`, and decimals hash numerically.
+    pub fn canonical_hash(&self) -> Result<u64, ImprintError> {
+        let mut buf = BytesMut::new();
+        self.write_canonical(&mut buf)?;
+        Ok(xxh3_64(&buf))
+    }
+
+    /// Whether two records hold the same live fields, by the same rules as
+    /// [`canonical_hash`](Self::canonical_hash).
+    pub fn logical_eq(&self, other: &ImprintRecord) -> Result<bool, ImprintError> {
+        if self.header.schema_id.fieldspace_id != other.header.schema_id.fieldspace_id
+            || self.directory.len() != other.directory.len()
+        {
+            return Ok(false);
+        }
+
+        for (entry, other_entry) in self.directory.iter().zip(&other.directory) {
+            if entry.id != other_entry.id
+                || entry.type_code.logical() != other_entry.type_code.logical()
+            {
+                return Ok(false);
+            }
+            // identical encodings are equal without decoding
+            if entry.type_code == other_entry.type_code
+                && self.get_raw_bytes(entry.id)? == other.get_raw_bytes(entry.id)?
+            {
+                continue;
+            }
+            if self.canonical_field(entry.id)? != other.canonical_field(entry.id)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn canonical_field(&self, field_id: u32) -> Result<BytesMut, ImprintError> {
+        let mut buf = BytesMut::new();
+        if let Some(value) = self.get_value(field_id)? {
+            write_canonical(&value, &mut buf)?;
+        }
+        Ok(buf)
+    }
+
+    fn write_canonical(&self, buf: &mut BytesMut) -> Result<(), ImprintError> {
+        buf.put_u32_le(self.header.schema_id.fieldspace_id);
+        varint::encode_len(self.directory.len(), buf)?;
+        for entry in &self.directory {
+            buf.put_u32_le(entry.id);
+            buf.put_u8(entry.type_code.logical() as u8);
+            if let Some(value) = self.get_value(entry.id)? {
+                write_canonical(&value, buf)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write a value in a self-delimiting form that is equal for logically equal values
+fn write_canonical(value: &Value, buf: &mut BytesMut) -> Result<(), ImprintError> {
+    match value {
+        Value::Float32(f) => buf.put_u32_le(canonical_f32(*f)),
+        Value::Float64(f) => buf.put_u64_le(canonical_f64(*f)),
+        Value::Decimal(d) => {
+            let (unscaled, scale) = d.normalized();
+            buf.put_u8(scale);
+            buf.put_i128_le(unscaled);
+        }
+        Value::FixedBytes(v) => {
+            varint::encode_len(v.len(), buf)?;
+            buf.put_slice(v);
+        }
+        Value::Array(values) => {
+            varint::encode_len(values.len(), buf)?;
+            for value in values {
+                write_canonical_tagged(value, buf)?;
+            }
+        }
+        Value::Map(map) => {
+            varint::encode_len(map.len(), buf)?;
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            for (key, value) in entries {
+                buf.put_u8(key.type_code() as u8);
+                key.write(buf)?;
+                write_canonical_tagged(value, buf)?;
+            }
+        }
+        Value::Row(record) => record.write_canonical(buf)?,
+        Value::Union(inner) => write_canonical_tagged(inner, buf)?,
+        other => other.write(buf)?,
+    }
+    Ok(())
+}
+
+fn write_canonical_tagged(value: &Value, buf: &mut BytesMut) -> Result<(), ImprintError> {
+    buf.put_u8(value.type_code() as u8);
+    write_canonical(value, buf)
+}
+
+pub(crate) fn canonical_f32(f: f32) -> u32 {
+    if f.is_nan() {
+        CANONICAL_NAN_F32
+    } else {
+        // adding zero turns -0.0 into 0.0 and leaves everything else unchanged
+        (f + 0.0).to_bits()
+    }
+}
+
+pub(crate) fn canonical_f64(f: f64) -> u64 {
+    if f.is_nan() {
+        CANONICAL_NAN_F64
+    } else {
+        (f + 0.0).to_bits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Decimal, ImprintWriter, IntegerEncoding, Merge, MergeOptions, types::MapKey,
+        types::SchemaId, writer::test_record,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn should_hash_equal_rows_equal_however_they_were_merged() {
+        // Given a row written directly
+        let direct = test_record(
+            IntegerEncoding::Fixed,
+            vec![(1, 42.into()), (2, "a".into()), (3, true.into())],
+        );
+
+        // And the same row assembled from a merge that leaves zombie bytes behind,
+        // under another schema hash and with varint integers
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0x2,
+        })
+        .unwrap();
+        writer.set_integer_encoding(IntegerEncoding::Varint);
+        writer.add_field(1, 42.into()).unwrap();
+        writer.add_field(2, "a".into()).unwrap();
+        let first = writer.build().unwrap();
+        let second = test_record(
+            IntegerEncoding::Fixed,
+            vec![(2, "stale".into()), (3, true.into())],
+        );
+        let merged = first
+            .merge_with_opts(&second, MergeOptions::default())
+            .unwrap();
+        assert!(merged.wasted_bytes() > 0);
+        assert_ne!(merged, direct);
+
+        // When hashing and comparing them
+        // Then they should be logically equal and hash equal
+        assert!(direct.logical_eq(&merged).unwrap());
+        assert_eq!(
+            direct.canonical_hash().unwrap(),
+            merged.canonical_hash().unwrap()
+        );
+
+        // And a row with a different value should differ
+        let other = test_record(
+            IntegerEncoding::Fixed,
+            vec![(1, 43.into()), (2, "a".into()), (3, true.into())],
+        );
+        assert!(!direct.logical_eq(&other).unwrap());
+        assert_ne!(
+            direct.canonical_hash().unwrap(),
+            other.canonical_hash().unwrap()
+        );
+    }
+
+    #[test]
+    fn should_canonicalize_floats_decimals_and_maps() {
+        // Given values that differ in bits or encoding but not in meaning
+        let map = |order: &[i32]| {
+            let m: HashMap<MapKey, Value> = order
+                .iter()
+                .map(|&k| (k.into(), Value::Float64(-0.0)))
+                .collect();
+            Value::Map(m)
+        };
+        let a = test_record(
+            IntegerEncoding::Fixed,
+            vec![
+                (1, Value::Float64(f64::NAN)),
+                (2, Value::Float32(-0.0)),
+                (3, Decimal::new(10, 3, 1).unwrap().into()),
+                (4, map(&[1, 2, 3])),
+                (
+                    5,
+                    Value::Array(vec![Value::Float64(-f64::NAN), Value::Null]),
+                ),
+            ],
+        );
+        let b = test_record(
+            IntegerEncoding::Fixed,
+            vec![
+                (1, Value::Float64(-f64::NAN)),
+                (2, Value::Float32(0.0)),
+                (3, Decimal::new(100, 10, 2).unwrap().into()),
+                (4, map(&[3, 2, 1])),
+                (5, Value::Array(vec![Value::Float64(f64::NAN), Value::Null])),
+            ],
+        );
+
+        // When comparing them
+        // Then they should be logically equal and hash equal
+        assert!(a.logical_eq(&b).unwrap());
+        assert_eq!(a.canonical_hash().unwrap(), b.canonical_hash().unwrap());
+    }
+
+    #[test]
+    fn should_distinguish_types_and_fieldspaces() {
+        let int = test_record(IntegerEncoding::Fixed, vec![(1, 1i32.into())]);
+        let long = test_record(IntegerEncoding::Fixed, vec![(1, 1i64.into())]);
+        assert!(!int.logical_eq(&long).unwrap());
+        assert_ne!(
+            int.canonical_hash().unwrap(),
+            long.canonical_hash().unwrap()
+        );
+
+        let mut other_fieldspace = int.clone();
+        other_fieldspace.header.schema_id.fieldspace_id = 2;
+        assert!(!int.logical_eq(&other_fieldspace).unwrap());
+        assert_ne!(
+            int.canonical_hash().unwrap(),
+            other_fieldspace.canonical_hash().unwrap()
+        );
+    }
+
+    #[test]
+    fn should_keep_canonical_hash_stable() {
+        // the hash is persisted by dedup stores, so it must never change
+        let row = test_record(
+            IntegerEncoding::Fixed,
+            vec![(1, 42.into()), (2, "hello".into())],
+        );
+        assert_eq!(row.canonical_hash().unwrap(), 1594229689808184651);
+    }
+}
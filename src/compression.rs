@@ -0,0 +1,156 @@
+use bytes::Bytes;
+
+use crate::error::ImprintError;
+
+/// Codec used to compress the payload of a record. The field directory is
+/// never compressed so that offsets can be resolved without decompressing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum Compression {
+    #[default]
+    None = 0x0,
+    Lz4 = 0x1,
+    Zstd = 0x2,
+}
+
+impl Compression {
+    /// Compress the given payload bytes with this codec
+    pub(crate) fn compress(&self, bytes: &[u8]) -> Result<Bytes, ImprintError> {
+        match self {
+            Self::None => Ok(Bytes::copy_from_slice(bytes)),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => Ok(lz4_flex::block::compress(bytes).into()),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => zstd::bulk::compress(bytes, 0)
+                .map(Bytes::from)
+                .map_err(|e| ImprintError::Compression(e.to_string())),
+            #[allow(unreachable_patterns)]
+            _ => Err(ImprintError::UnsupportedCompression(*self as u8)),
+        }
+    }
+
+    /// Largest size that `compressed_len` bytes can decompress to with this codec
+    fn max_decompressed_size(&self, compressed_len: usize) -> usize {
+        let ratio = match self {
+            Self::None => 1,
+            // a match can't encode more than 255 bytes per byte of its length
+            Self::Lz4 => 255,
+            // a 4 byte run-length block holds at most a whole 128 KiB block
+            Self::Zstd => 32 * 1024,
+        };
+        compressed_len.saturating_mul(ratio)
+    }
+
+    /// Decompress the given bytes with this codec, expecting exactly `size` bytes of output
+    pub(crate) fn decompress(&self, bytes: &[u8], size: usize) -> Result<Bytes, ImprintError> {
+        // the size comes from the record, so check it before allocating the output
+        if size > self.max_decompressed_size(bytes.len()) {
+            return Err(ImprintError::Compression(format!(
+                "{} compressed bytes can't decompress to {} bytes",
+                bytes.len(),
+                size
+            )));
+        }
+
+        let decompressed: Bytes = match self {
+            Self::None => Bytes::copy_from_slice(bytes),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => lz4_flex::block::decompress(bytes, size)
+                .map_err(|e| ImprintError::Compression(e.to_string()))?
+                .into(),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => zstd::bulk::decompress(bytes, size)
+                .map_err(|e| ImprintError::Compression(e.to_string()))?
+                .into(),
+            #[allow(unreachable_patterns)]
+            _ => return Err(ImprintError::UnsupportedCompression(*self as u8)),
+        };
+
+        if decompressed.len() != size {
+            return Err(ImprintError::Compression(format!(
+                "expected {} decompressed bytes, got {}",
+                size,
+                decompressed.len()
+            )));
+        }
+        Ok(decompressed)
+    }
+}
+
+impl TryFrom<u8> for Compression {
+    type Error = ImprintError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(Self::None),
+            0x1 => Ok(Self::Lz4),
+            0x2 => Ok(Self::Zstd),
+            _ => Err(ImprintError::UnsupportedCompression(value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(codec: Compression) {
+        // Given a highly compressible payload
+        let payload = "lorem ipsum dolor sit amet ".repeat(100).into_bytes();
+
+        // When compressing and decompressing it
+        let compressed = codec.compress(&payload).unwrap();
+        let decompressed = codec.decompress(&compressed, payload.len()).unwrap();
+
+        // Then the payload should survive the roundtrip
+        assert_eq!(&decompressed[..], &payload[..]);
+        if codec != Compression::None {
+            assert!(compressed.len() < payload.len());
+        }
+    }
+
+    #[test]
+    fn should_roundtrip_without_compression() {
+        roundtrip(Compression::None);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn should_roundtrip_lz4() {
+        roundtrip(Compression::Lz4);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn should_roundtrip_zstd() {
+        roundtrip(Compression::Zstd);
+    }
+
+    #[test]
+    fn should_reject_unknown_codec() {
+        assert!(matches!(
+            Compression::try_from(0x7f),
+            Err(ImprintError::UnsupportedCompression(0x7f))
+        ));
+    }
+
+    #[test]
+    fn should_reject_sizes_past_the_codec_ratio() {
+        // Given a few compressed bytes claiming to decompress to 4 GiB
+        for codec in [Compression::None, Compression::Lz4, Compression::Zstd] {
+            // Then they should be rejected before decompressing, whether or not the codec is built
+            assert!(matches!(
+                codec.decompress(&[0; 8], u32::MAX as usize),
+                Err(ImprintError::Compression(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn should_reject_size_mismatch() {
+        assert!(matches!(
+            Compression::None.decompress(&[1, 2, 3], 4),
+            Err(ImprintError::Compression(_))
+        ));
+    }
+}
@@ -0,0 +1,227 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use crate::{error::ImprintError, types::Value};
+
+/// A fixed-point decimal number: `unscaled * 10^-scale`, with at most
+/// `precision` significant digits.
+///
+/// Equality, hashing and ordering are numeric, so `1.0` and `1.00` are equal
+/// and hash the same regardless of their scale and precision. This makes
+/// decimals usable as join keys across sources that declare different scales.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    unscaled: i128,
+    precision: u8,
+    scale: u8,
+}
+
+impl Decimal {
+    /// Largest precision representable by the 128-bit unscaled value
+    pub const MAX_PRECISION: u8 = 38;
+
+    pub fn new(unscaled: i128, precision: u8, scale: u8) -> Result<Self, ImprintError> {
+        if precision == 0 || precision > Self::MAX_PRECISION {
+            return Err(ImprintError::SchemaError(format!(
+                "decimal precision must be between 1 and {}, got {}",
+                Self::MAX_PRECISION,
+                precision
+            )));
+        }
+        if scale > precision {
+            return Err(ImprintError::SchemaError(format!(
+                "decimal scale {} exceeds precision {}",
+                scale, precision
+            )));
+        }
+        if unscaled.unsigned_abs() >= 10u128.pow(precision as u32) {
+            return Err(ImprintError::SchemaError(format!(
+                "decimal {} exceeds precision {}",
+                unscaled, precision
+            )));
+        }
+        Ok(Self {
+            unscaled,
+            precision,
+            scale,
+        })
+    }
+
+    pub fn unscaled(&self) -> i128 {
+        self.unscaled
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+
+    /// The unscaled value and scale with trailing fractional zeros removed
+    pub(crate) fn normalized(&self) -> (i128, u8) {
+        let (mut unscaled, mut scale) = (self.unscaled, self.scale);
+        while scale > 0 && unscaled % 10 == 0 {
+            unscaled /= 10;
+            scale -= 1;
+        }
+        (unscaled, scale)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized() == other.normalized()
+    }
+}
+
+impl Eq for Decimal {}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state);
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // compare the integral parts first and then the fractional parts at a common
+        // scale, which can't overflow since both fit in MAX_PRECISION digits
+        let split = |d: &Decimal| {
+            let factor = 10i128.pow(d.scale as u32);
+            (d.unscaled.div_euclid(factor), d.unscaled.rem_euclid(factor))
+        };
+        let (self_int, self_frac) = split(self);
+        let (other_int, other_frac) = split(other);
+
+        let scale = self.scale.max(other.scale);
+        let self_frac = self_frac * 10i128.pow((scale - self.scale) as u32);
+        let other_frac = other_frac * 10i128.pow((scale - other.scale) as u32);
+        (self_int, self_frac).cmp(&(other_int, other_frac))
+    }
+}
+
+impl From<Decimal> for Value {
+    fn from(d: Decimal) -> Value {
+        Value::Decimal(d)
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+mod rust_decimal_impls {
+    use super::*;
+
+    impl From<rust_decimal::Decimal> for Decimal {
+        fn from(d: rust_decimal::Decimal) -> Self {
+            let unscaled = d.mantissa();
+            let scale = d.scale() as u8;
+            let digits = unscaled
+                .unsigned_abs()
+                .checked_ilog10()
+                .map_or(1, |d| d + 1) as u8;
+            Self {
+                unscaled,
+                precision: digits.max(scale).max(1),
+                scale,
+            }
+        }
+    }
+
+    impl TryFrom<Decimal> for rust_decimal::Decimal {
+        type Error = ImprintError;
+
+        fn try_from(d: Decimal) -> Result<Self, Self::Error> {
+            rust_decimal::Decimal::try_from_i128_with_scale(d.unscaled, d.scale as u32)
+                .map_err(|e| ImprintError::SchemaError(format!("decimal out of range: {}", e)))
+        }
+    }
+
+    impl From<rust_decimal::Decimal> for Value {
+        fn from(d: rust_decimal::Decimal) -> Value {
+            Value::Decimal(d.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash(d: &Decimal) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        d.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn should_compare_numerically_across_scales() {
+        // Given the same number at different scales and precisions
+        let a = Decimal::new(10, 3, 1).unwrap(); // 1.0
+        let b = Decimal::new(100, 10, 2).unwrap(); // 1.00
+
+        // Then they should be equal and hash the same
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+
+        // And ordering should be numeric
+        let cases = [
+            // -1.5 < -1
+            (
+                Decimal::new(-15, 2, 1).unwrap(),
+                Decimal::new(-1, 1, 0).unwrap(),
+            ),
+            // -1 < -0.99
+            (
+                Decimal::new(-1, 1, 0).unwrap(),
+                Decimal::new(-99, 2, 2).unwrap(),
+            ),
+            // 1.99 < 2
+            (
+                Decimal::new(199, 3, 2).unwrap(),
+                Decimal::new(2, 1, 0).unwrap(),
+            ),
+            // 1e-38 < 1e37
+            (
+                Decimal::new(1, 38, 38).unwrap(),
+                Decimal::new(10i128.pow(37), 38, 0).unwrap(),
+            ),
+        ];
+        for (smaller, larger) in cases {
+            assert!(smaller < larger, "{:?} should be < {:?}", smaller, larger);
+            assert!(larger > smaller, "{:?} should be > {:?}", larger, smaller);
+        }
+    }
+
+    #[test]
+    fn should_reject_invalid_decimals() {
+        assert!(Decimal::new(1, 0, 0).is_err());
+        assert!(Decimal::new(1, 39, 0).is_err());
+        assert!(Decimal::new(1, 2, 3).is_err());
+        assert!(Decimal::new(100, 2, 0).is_err());
+        assert!(Decimal::new(-99, 2, 0).is_ok());
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn should_convert_rust_decimal() {
+        // Given a rust_decimal value
+        let d = rust_decimal::Decimal::new(-12345, 3);
+
+        // When converting it to an imprint decimal and back
+        let converted = Decimal::from(d);
+
+        // Then it should be preserved
+        assert_eq!(converted.unscaled(), -12345);
+        assert_eq!(converted.scale(), 3);
+        assert_eq!(converted.precision(), 5);
+        assert_eq!(rust_decimal::Decimal::try_from(converted).unwrap(), d);
+    }
+}
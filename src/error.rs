@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::types::TypeCode;
+
 #[derive(Error, Debug)]
 pub enum ImprintError {
     #[error("invalid magic byte: expected 0x49, got {0:#x}")]
@@ -14,6 +16,22 @@ pub enum ImprintError {
     #[error("invalid varint encoding")]
     InvalidVarInt,
 
+    #[error("type mismatch: expected {expected:?}, got {actual:?}")]
+    TypeMismatch {
+        expected: TypeCode,
+        actual: TypeCode,
+    },
+
+    #[error("field {field_id} is {first:?} in the first record but {second:?} in the second")]
+    MergeConflict {
+        field_id: u32,
+        first: TypeCode,
+        second: TypeCode,
+    },
+
+    #[error("{what} of {value} exceeds the u32 range of the format")]
+    Overflow { what: &'static str, value: u64 },
+
     #[error("field not found: {0}")]
     FieldNotFound(u32),
 
@@ -26,6 +44,21 @@ pub enum ImprintError {
     #[error("schema error: {0}")]
     SchemaError(String),
 
+    #[error("unsupported compression codec: {0:#x}")]
+    UnsupportedCompression(u8),
+
+    #[error("compression error: {0}")]
+    Compression(String),
+
+    #[error("checksum mismatch: expected {expected:#x}, got {actual:#x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+
+    #[error("invalid sort key: {0}")]
+    InvalidSortKey(String),
+
+    #[error("expression error: {0}")]
+    Expression(String),
+
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -0,0 +1,1035 @@
+use crate::{
+    error::ImprintError,
+    schema::SchemaRegistry,
+    types::{ImprintRecord, MapKey, TypeCode, Value},
+};
+
+/// An expression computing a value from the fields of a record, such as
+/// `#3 * #4` or `coalesce(nickname, first_name || ' ' || last_name)`.
+///
+/// Expressions are usually parsed from text, where:
+///
+/// - `#3` is the field with ID 3, and a bare name is a field registered under
+///   that name in the fieldspace (see [`Expr::parse_with_names`])
+/// - `base[i]` is the element of an array at position `i`, the value of a map
+///   at key `i` or the field of a nested row with ID `i`, and `base.key` and
+///   `base.#2` are shorthands for `base["key"]` and `base[2]`
+/// - `+`, `-`, `*`, `/` and `%` are arithmetic, and `||` concatenates strings
+/// - `concat`, `upper`, `lower`, `trim`, `length`, `substr` (with 1-based
+///   positions) and `coalesce` are functions, and `cast(x as int64)` converts
+///   between numbers, strings and booleans
+/// - literals are numbers, `'strings'` or `"strings"`, `true`, `false` and `null`
+///
+/// Missing fields, keys and positions are null, and operators and functions
+/// return null when an operand is null, except for `coalesce`. Integers of
+/// different types are widened to the wider one before arithmetic, which fails
+/// on overflow, and mixing integers and floats gives a `Float64`. Union values
+/// are unwrapped to their branch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    /// A top-level field of the record
+    Field(u32),
+    /// An element of an array, a value of a map or a field of a nested row
+    Index(Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+    Cast(Box<Expr>, TypeCode),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Concat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Concat,
+    Upper,
+    Lower,
+    Trim,
+    Length,
+    Substr,
+    Coalesce,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "concat" => Some(Function::Concat),
+            "upper" => Some(Function::Upper),
+            "lower" => Some(Function::Lower),
+            "trim" => Some(Function::Trim),
+            "length" => Some(Function::Length),
+            "substr" => Some(Function::Substr),
+            "coalesce" => Some(Function::Coalesce),
+            _ => None,
+        }
+    }
+
+    /// The smallest and largest number of arguments the function takes
+    fn arity(&self) -> (usize, usize) {
+        match self {
+            Function::Concat | Function::Coalesce => (1, usize::MAX),
+            Function::Upper | Function::Lower | Function::Trim | Function::Length => (1, 1),
+            Function::Substr => (2, 3),
+        }
+    }
+}
+
+impl Expr {
+    /// Parse an expression that refers to fields by ID only
+    pub fn parse(source: &str) -> Result<Expr, ImprintError> {
+        Parser::new(source, None)?.parse()
+    }
+
+    /// Parse an expression that may also refer to fields by the names
+    /// registered for them in the given fieldspace. Fields of nested rows are
+    /// referred to by ID, since their fieldspace is only known once read.
+    pub fn parse_with_names(
+        source: &str,
+        registry: &SchemaRegistry,
+        fieldspace_id: u32,
+    ) -> Result<Expr, ImprintError> {
+        Parser::new(source, Some((registry, fieldspace_id)))?.parse()
+    }
+
+    /// Evaluate the expression against a record
+    pub fn eval(&self, record: &ImprintRecord) -> Result<Value, ImprintError> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Field(field_id) => Ok(unwrap_union(
+                record
+                    .get_value(*field_id)?
+                    .filter(|value| *value != Value::Tombstone)
+                    .unwrap_or(Value::Null),
+            )),
+            Expr::Index(base, index) => index_value(base.eval(record)?, index.eval(record)?),
+            Expr::Neg(operand) => negate(operand.eval(record)?),
+            Expr::Binary(op, left, right) => binary(*op, left.eval(record)?, right.eval(record)?),
+            Expr::Call(Function::Coalesce, args) => {
+                // later arguments are only evaluated when needed
+                for arg in args {
+                    let value = arg.eval(record)?;
+                    if value != Value::Null {
+                        return Ok(value);
+                    }
+                }
+                Ok(Value::Null)
+            }
+            Expr::Call(function, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(record))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call(*function, args)
+            }
+            Expr::Cast(operand, type_code) => cast(operand.eval(record)?, *type_code),
+        }
+    }
+}
+
+fn unwrap_union(value: Value) -> Value {
+    match value {
+        Value::Union(inner) => *inner,
+        value => value,
+    }
+}
+
+fn index_value(base: Value, index: Value) -> Result<Value, ImprintError> {
+    let value = match (base, index) {
+        (Value::Null, _) | (_, Value::Null) => Value::Null,
+        (Value::Array(mut values), index) => {
+            let position = integer(&index)?;
+            match usize::try_from(position) {
+                Ok(position) if position < values.len() => values.swap_remove(position),
+                _ => Value::Null,
+            }
+        }
+        (Value::Map(mut map), key) => {
+            // integer keys are widened to the key type of the map, so that `m[1]`
+            // finds the key 1 of a map keyed by Int64
+            let key_type = map.keys().next().map(MapKey::type_code);
+            let key = match key_type {
+                Some(key_type)
+                    if key.as_integer().is_some() && key.type_code().widens_to(key_type) =>
+                {
+                    key.widen(key_type)?
+                }
+                _ => key,
+            };
+            map.remove(&MapKey::try_from(key)?).unwrap_or(Value::Null)
+        }
+        (Value::Row(row), index) => {
+            let field_id = u32::try_from(integer(&index)?)
+                .map_err(|_| ImprintError::Expression(format!("invalid field ID {:?}", index)))?;
+            row.get_value(field_id)?.unwrap_or(Value::Null)
+        }
+        (base, _) => {
+            return Err(ImprintError::Expression(format!(
+                "{:?} values can't be indexed",
+                base.type_code()
+            )));
+        }
+    };
+    Ok(unwrap_union(value))
+}
+
+fn integer(value: &Value) -> Result<i128, ImprintError> {
+    value.as_integer().ok_or(ImprintError::TypeMismatch {
+        expected: TypeCode::Int64,
+        actual: value.type_code(),
+    })
+}
+
+/// The value of an integer type holding `i`, if it's in that type's range
+fn from_integer(type_code: TypeCode, i: i128) -> Option<Value> {
+    Some(match type_code {
+        TypeCode::Int8 => Value::Int8(i8::try_from(i).ok()?),
+        TypeCode::Int16 => Value::Int16(i16::try_from(i).ok()?),
+        TypeCode::Int32 => Value::Int32(i32::try_from(i).ok()?),
+        TypeCode::Int64 => Value::Int64(i64::try_from(i).ok()?),
+        TypeCode::UInt8 => Value::UInt8(u8::try_from(i).ok()?),
+        TypeCode::UInt16 => Value::UInt16(u16::try_from(i).ok()?),
+        TypeCode::UInt32 => Value::UInt32(u32::try_from(i).ok()?),
+        TypeCode::UInt64 => Value::UInt64(u64::try_from(i).ok()?),
+        _ => return None,
+    })
+}
+
+fn overflow(type_code: TypeCode) -> ImprintError {
+    ImprintError::Expression(format!("{:?} overflow", type_code))
+}
+
+fn float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float32(f) => Some(*f as f64),
+        Value::Float64(f) => Some(*f),
+        value => value.as_integer().map(|i| i as f64),
+    }
+}
+
+fn negate(value: Value) -> Result<Value, ImprintError> {
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::Float32(f) => Ok(Value::Float32(-f)),
+        Value::Float64(f) => Ok(Value::Float64(-f)),
+        value => {
+            let type_code = value.type_code();
+            from_integer(type_code, -integer(&value)?).ok_or(overflow(type_code))
+        }
+    }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, ImprintError> {
+    if left == Value::Null || right == Value::Null {
+        return Ok(Value::Null);
+    }
+    if op == BinaryOp::Concat {
+        return call(Function::Concat, vec![left, right]);
+    }
+
+    let is_float = |v: &Value| matches!(v, Value::Float32(_) | Value::Float64(_));
+    if is_float(&left) || is_float(&right) {
+        let not_a_number = |v: &Value| ImprintError::TypeMismatch {
+            expected: TypeCode::Float64,
+            actual: v.type_code(),
+        };
+        let x = float(&left).ok_or_else(|| not_a_number(&left))?;
+        let y = float(&right).ok_or_else(|| not_a_number(&right))?;
+        let result = match op {
+            BinaryOp::Add => x + y,
+            BinaryOp::Sub => x - y,
+            BinaryOp::Mul => x * y,
+            BinaryOp::Div => x / y,
+            BinaryOp::Rem => x % y,
+            BinaryOp::Concat => unreachable!("handled above"),
+        };
+        // f32 operations computed in f64 and rounded back are correctly rounded
+        return Ok(match (&left, &right) {
+            (Value::Float32(_), Value::Float32(_)) => Value::Float32(result as f32),
+            _ => Value::Float64(result),
+        });
+    }
+
+    let (x, y) = (integer(&left)?, integer(&right)?);
+    let (left_type, right_type) = (left.type_code(), right.type_code());
+    let target = if left_type.widens_to(right_type) {
+        right_type
+    } else if right_type.widens_to(left_type) {
+        left_type
+    } else if left_type.widens_to(TypeCode::Int64) && right_type.widens_to(TypeCode::Int64) {
+        // e.g. Int32 and UInt32, neither of which holds the other
+        TypeCode::Int64
+    } else {
+        return Err(ImprintError::Expression(format!(
+            "no integer type holds both {:?} and {:?}",
+            left_type, right_type
+        )));
+    };
+    if matches!(op, BinaryOp::Div | BinaryOp::Rem) && y == 0 {
+        return Err(ImprintError::Expression("division by zero".into()));
+    }
+    let result = match op {
+        BinaryOp::Add => x.checked_add(y),
+        BinaryOp::Sub => x.checked_sub(y),
+        BinaryOp::Mul => x.checked_mul(y),
+        BinaryOp::Div => x.checked_div(y),
+        BinaryOp::Rem => x.checked_rem(y),
+        BinaryOp::Concat => unreachable!("handled above"),
+    };
+    result
+        .and_then(|i| from_integer(target, i))
+        .ok_or(overflow(target))
+}
+
+fn string(value: Value) -> Result<String, ImprintError> {
+    match value {
+        Value::String(s) => Ok(s),
+        value => Err(ImprintError::TypeMismatch {
+            expected: TypeCode::String,
+            actual: value.type_code(),
+        }),
+    }
+}
+
+fn call(function: Function, args: Vec<Value>) -> Result<Value, ImprintError> {
+    if args.contains(&Value::Null) {
+        return Ok(Value::Null);
+    }
+    let mut args = args.into_iter();
+    let value = match function {
+        Function::Concat => {
+            let mut result = String::new();
+            for arg in args {
+                result.push_str(&string(arg)?);
+            }
+            Value::String(result)
+        }
+        Function::Upper => Value::String(string(next_arg(&mut args)?)?.to_uppercase()),
+        Function::Lower => Value::String(string(next_arg(&mut args)?)?.to_lowercase()),
+        Function::Trim => Value::String(string(next_arg(&mut args)?)?.trim().to_string()),
+        Function::Length => {
+            let len = match next_arg(&mut args)? {
+                Value::String(s) => s.chars().count(),
+                Value::Bytes(v) | Value::FixedBytes(v) => v.len(),
+                Value::Array(v) => v.len(),
+                Value::Map(m) => m.len(),
+                value => {
+                    return Err(ImprintError::TypeMismatch {
+                        expected: TypeCode::String,
+                        actual: value.type_code(),
+                    });
+                }
+            };
+            Value::Int64(len as i64)
+        }
+        Function::Substr => {
+            let s = string(next_arg(&mut args)?)?;
+            let start = integer(&next_arg(&mut args)?)?;
+            let len = match args.next() {
+                Some(len) => usize::try_from(integer(&len)?)
+                    .map_err(|_| ImprintError::Expression("negative substr length".into()))?,
+                None => usize::MAX,
+            };
+            // positions before the start of the string shorten the substring, as in SQL
+            let skip = usize::try_from(start.max(1) - 1).unwrap_or(usize::MAX);
+            let len = len.saturating_sub(usize::try_from(1 - start.min(1)).unwrap_or(usize::MAX));
+            Value::String(s.chars().skip(skip).take(len).collect())
+        }
+        Function::Coalesce => unreachable!("evaluated lazily"),
+    };
+    Ok(value)
+}
+
+fn next_arg(args: &mut impl Iterator<Item = Value>) -> Result<Value, ImprintError> {
+    args.next()
+        .ok_or_else(|| ImprintError::Expression("missing function argument".into()))
+}
+
+fn cast(value: Value, target: TypeCode) -> Result<Value, ImprintError> {
+    let target = target.logical();
+    let actual = value.type_code();
+    if value == Value::Null || actual == target {
+        return Ok(value);
+    }
+    let mismatch = ImprintError::TypeMismatch {
+        expected: target,
+        actual,
+    };
+    let invalid =
+        |what: &str| ImprintError::Expression(format!("can't cast {} to {:?}", what, target));
+
+    match target {
+        TypeCode::Bool => match value {
+            Value::String(s) if s.eq_ignore_ascii_case("true") => Ok(Value::Bool(true)),
+            Value::String(s) if s.eq_ignore_ascii_case("false") => Ok(Value::Bool(false)),
+            Value::String(s) => Err(invalid(&format!("{:?}", s))),
+            value => Ok(Value::Bool(value.as_integer().ok_or(mismatch)? != 0)),
+        },
+        TypeCode::Float32 | TypeCode::Float64 => {
+            let f = match value {
+                Value::String(s) => s.trim().parse().map_err(|_| invalid(&format!("{:?}", s)))?,
+                value => float(&value).ok_or(mismatch)?,
+            };
+            Ok(match target {
+                TypeCode::Float32 => Value::Float32(f as f32),
+                _ => Value::Float64(f),
+            })
+        }
+        TypeCode::String => Ok(Value::String(match value {
+            Value::Bool(b) => b.to_string(),
+            Value::Float32(f) => f.to_string(),
+            Value::Float64(f) => f.to_string(),
+            Value::Bytes(v) => String::from_utf8(v).map_err(|_| ImprintError::InvalidUtf8String)?,
+            value => value.as_integer().ok_or(mismatch)?.to_string(),
+        })),
+        target if from_integer(target, 0).is_some() => {
+            let i = match value {
+                Value::Bool(b) => b as i128,
+                Value::Float32(f) if f.is_finite() => f.trunc() as i128,
+                Value::Float64(f) if f.is_finite() => f.trunc() as i128,
+                Value::String(s) => s.trim().parse().map_err(|_| invalid(&format!("{:?}", s)))?,
+                value => value.as_integer().ok_or(mismatch)?,
+            };
+            from_integer(target, i).ok_or_else(|| invalid(&i.to_string()))
+        }
+        _ => Err(mismatch),
+    }
+}
+
+fn type_from_name(name: &str) -> Option<TypeCode> {
+    match name {
+        "bool" => Some(TypeCode::Bool),
+        "int8" => Some(TypeCode::Int8),
+        "int16" => Some(TypeCode::Int16),
+        "int32" => Some(TypeCode::Int32),
+        "int64" => Some(TypeCode::Int64),
+        "uint8" => Some(TypeCode::UInt8),
+        "uint16" => Some(TypeCode::UInt16),
+        "uint32" => Some(TypeCode::UInt32),
+        "uint64" => Some(TypeCode::UInt64),
+        "float32" => Some(TypeCode::Float32),
+        "float64" => Some(TypeCode::Float64),
+        "string" => Some(TypeCode::String),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Integer(u128),
+    Float(f64),
+    String(String),
+    Ident(String),
+    FieldId(u32),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 12] = ["||", "+", "-", "*", "/", "%", "(", ")", "[", "]", ".", ","];
+
+/// Split an expression into tokens, each with the byte position it starts at
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ImprintError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    let take_while = |chars: &mut std::iter::Peekable<std::str::CharIndices>,
+                      start: usize,
+                      f: fn(char) -> bool| {
+        let mut end = start;
+        while let Some(&(i, c)) = chars.peek() {
+            if !f(c) {
+                break;
+            }
+            end = i + c.len_utf8();
+            chars.next();
+        }
+        &source[start..end]
+    };
+
+    while let Some(&(start, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '0'..='9' => {
+                let number =
+                    take_while(&mut chars, start, |c| c.is_ascii_alphanumeric() || c == '.');
+                // exponents can be signed
+                let number = match chars.peek() {
+                    Some(&(_, '+' | '-')) if number.ends_with(['e', 'E']) => {
+                        chars.next();
+                        take_while(&mut chars, start, |c| c.is_ascii_digit())
+                    }
+                    _ => number,
+                };
+                if number.chars().all(|c| c.is_ascii_digit()) {
+                    Token::Integer(number.parse().map_err(|_| {
+                        ImprintError::Expression(format!(
+                            "integer {} at {} is too large",
+                            number, start
+                        ))
+                    })?)
+                } else {
+                    Token::Float(number.parse().map_err(|_| {
+                        ImprintError::Expression(format!("invalid number {} at {}", number, start))
+                    })?)
+                }
+            }
+            '#' => {
+                chars.next();
+                let digits = take_while(&mut chars, start + 1, |c| c.is_ascii_digit());
+                Token::FieldId(digits.parse().map_err(|_| {
+                    ImprintError::Expression(format!("invalid field ID at {}", start))
+                })?)
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => s.push('\n'),
+                            Some((_, 't')) => s.push('\t'),
+                            Some((_, escaped)) => s.push(escaped),
+                            None => break,
+                        },
+                        Some((_, other)) => s.push(other),
+                        None => {
+                            return Err(ImprintError::Expression(format!(
+                                "unterminated string at {}",
+                                start
+                            )));
+                        }
+                    }
+                }
+                Token::String(s)
+            }
+            c if c.is_alphabetic() || c == '_' => Token::Ident(
+                take_while(&mut chars, start, |c| c.is_alphanumeric() || c == '_').to_string(),
+            ),
+            _ => {
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|symbol| source[start..].starts_with(*symbol))
+                    .ok_or_else(|| {
+                        ImprintError::Expression(format!(
+                            "unexpected character {:?} at {}",
+                            c, start
+                        ))
+                    })?;
+                for _ in 0..symbol.len() {
+                    chars.next();
+                }
+                Token::Symbol(symbol)
+            }
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+/// How deeply operands may nest, through unary minus, parentheses, indexes,
+/// function arguments and chains of binary operators, before parsing fails rather
+/// than overflowing the stack when the expression is evaluated or dropped
+const MAX_NESTING: usize = 128;
+
+/// A recursive descent parser, from the loosest binding operator to the tightest
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    end: usize,
+    names: Option<(&'a SchemaRegistry, u32)>,
+    /// Number of operands currently being parsed, one inside the other
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &str, names: Option<(&'a SchemaRegistry, u32)>) -> Result<Self, ImprintError> {
+        Ok(Self {
+            tokens: tokenize(source)?,
+            position: 0,
+            end: source.len(),
+            names,
+            depth: 0,
+        })
+    }
+
+    fn parse(mut self) -> Result<Expr, ImprintError> {
+        let expr = self.parse_concat()?;
+        match self.tokens.get(self.position) {
+            Some((at, token)) => Err(ImprintError::Expression(format!(
+                "unexpected {:?} at {}",
+                token, at
+            ))),
+            None => Ok(expr),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn at(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |(at, _)| *at)
+    }
+
+    fn next(&mut self) -> Result<Token, ImprintError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(_, token)| token.clone())
+            .ok_or_else(|| ImprintError::Expression("unexpected end of expression".into()))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ImprintError> {
+        if !self.eat(symbol) {
+            return Err(ImprintError::Expression(format!(
+                "expected {:?} at {}",
+                symbol,
+                self.at()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Go one level deeper into the expression, failing past [`MAX_NESTING`]. Each
+    /// operator of a chain like `a + b + c` puts the operands before it one level
+    /// deeper in the left-nested tree it builds, so chains count towards the depth.
+    fn nest(&mut self) -> Result<(), ImprintError> {
+        if self.depth == MAX_NESTING {
+            return Err(ImprintError::Expression(format!(
+                "expression nested more than {} deep at {}",
+                MAX_NESTING,
+                self.at()
+            )));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn parse_concat(&mut self) -> Result<Expr, ImprintError> {
+        let depth = self.depth;
+        let mut left = self.parse_additive()?;
+        while self.eat("||") {
+            self.nest()?;
+            let right = self.parse_additive()?;
+            left = Expr::Binary(BinaryOp::Concat, Box::new(left), Box::new(right));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, ImprintError> {
+        let depth = self.depth;
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op = if self.eat("+") {
+                BinaryOp::Add
+            } else if self.eat("-") {
+                BinaryOp::Sub
+            } else {
+                break;
+            };
+            self.nest()?;
+            let right = self.parse_multiplicative()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, ImprintError> {
+        let depth = self.depth;
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = if self.eat("*") {
+                BinaryOp::Mul
+            } else if self.eat("/") {
+                BinaryOp::Div
+            } else if self.eat("%") {
+                BinaryOp::Rem
+            } else {
+                break;
+            };
+            self.nest()?;
+            let right = self.parse_unary()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ImprintError> {
+        // every nested operand is parsed through here, so this bounds the recursion
+        self.nest()?;
+        let expr = self.parse_negation();
+        self.depth -= 1;
+        expr
+    }
+
+    fn parse_negation(&mut self) -> Result<Expr, ImprintError> {
+        if !self.eat("-") {
+            return self.parse_postfix();
+        }
+        // negative literals are folded so that e.g. i32::MIN stays an Int32
+        match self.peek() {
+            Some(Token::Integer(n)) => {
+                let n = *n;
+                self.position += 1;
+                let i = i128::try_from(n).map_err(|_| {
+                    ImprintError::Expression(format!("integer -{} is too small", n))
+                })?;
+                Ok(Expr::Literal(integer_literal(-i)?))
+            }
+            Some(Token::Float(f)) => {
+                let f = *f;
+                self.position += 1;
+                Ok(Expr::Literal(Value::Float64(-f)))
+            }
+            _ => Ok(Expr::Neg(Box::new(self.parse_unary()?))),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, ImprintError> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            let index = if self.eat("[") {
+                let index = self.parse_concat()?;
+                self.expect("]")?;
+                index
+            } else if self.eat(".") {
+                let at = self.at();
+                match self.next()? {
+                    Token::Ident(key) => Expr::Literal(Value::String(key)),
+                    Token::FieldId(field_id) => Expr::Literal(Value::UInt32(field_id)),
+                    token => {
+                        return Err(ImprintError::Expression(format!(
+                            "expected a key or field ID after '.' at {}, got {:?}",
+                            at, token
+                        )));
+                    }
+                }
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Index(Box::new(expr), Box::new(index));
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ImprintError> {
+        let at = self.at();
+        match self.next()? {
+            Token::Integer(n) => {
+                let i = i128::try_from(n)
+                    .map_err(|_| ImprintError::Expression(format!("integer {} is too large", n)))?;
+                Ok(Expr::Literal(integer_literal(i)?))
+            }
+            Token::Float(f) => Ok(Expr::Literal(Value::Float64(f))),
+            Token::String(s) => Ok(Expr::Literal(Value::String(s))),
+            Token::FieldId(field_id) => Ok(Expr::Field(field_id)),
+            Token::Symbol("(") => {
+                let expr = self.parse_concat()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Ident(name) => {
+                let lower = name.to_ascii_lowercase();
+                match lower.as_str() {
+                    "true" => return Ok(Expr::Literal(Value::Bool(true))),
+                    "false" => return Ok(Expr::Literal(Value::Bool(false))),
+                    "null" => return Ok(Expr::Literal(Value::Null)),
+                    _ => {}
+                }
+                if !self.eat("(") {
+                    return self
+                        .names
+                        .and_then(|(registry, fieldspace_id)| {
+                            registry.field_id(fieldspace_id, &name)
+                        })
+                        .map(Expr::Field)
+                        .ok_or_else(|| {
+                            ImprintError::Expression(format!("unknown field {:?} at {}", name, at))
+                        });
+                }
+                if lower == "cast" {
+                    return self.parse_cast();
+                }
+
+                let function = Function::from_name(&lower).ok_or_else(|| {
+                    ImprintError::Expression(format!("unknown function {:?} at {}", name, at))
+                })?;
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.parse_concat()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                let (min, max) = function.arity();
+                if args.len() < min || args.len() > max {
+                    return Err(ImprintError::Expression(format!(
+                        "{} takes {} arguments, got {} at {}",
+                        lower,
+                        if min == max {
+                            min.to_string()
+                        } else {
+                            format!("at least {}", min)
+                        },
+                        args.len(),
+                        at
+                    )));
+                }
+                Ok(Expr::Call(function, args))
+            }
+            token => Err(ImprintError::Expression(format!(
+                "unexpected {:?} at {}",
+                token, at
+            ))),
+        }
+    }
+
+    /// Parse the rest of `cast(expr as type)` after the opening parenthesis
+    fn parse_cast(&mut self) -> Result<Expr, ImprintError> {
+        let expr = self.parse_concat()?;
+        let at = self.at();
+        match self.next()? {
+            Token::Ident(keyword) if keyword.eq_ignore_ascii_case("as") => {}
+            _ => return Err(ImprintError::Expression(format!("expected 'as' at {}", at))),
+        }
+        let at = self.at();
+        let type_code = match self.next()? {
+            Token::Ident(name) => type_from_name(&name.to_ascii_lowercase()),
+            _ => None,
+        }
+        .ok_or_else(|| ImprintError::Expression(format!("expected a type name at {}", at)))?;
+        self.expect(")")?;
+        Ok(Expr::Cast(Box::new(expr), type_code))
+    }
+}
+
+/// The narrowest of Int32, Int64 and UInt64 that holds an integer literal
+fn integer_literal(i: i128) -> Result<Value, ImprintError> {
+    [TypeCode::Int32, TypeCode::Int64, TypeCode::UInt64]
+        .into_iter()
+        .find_map(|type_code| from_integer(type_code, i))
+        .ok_or_else(|| ImprintError::Expression(format!("integer {} is out of range", i)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntegerEncoding, writer::test_record};
+    use std::collections::HashMap;
+
+    fn eval(source: &str, record: &ImprintRecord) -> Result<Value, ImprintError> {
+        Expr::parse(source)?.eval(record)
+    }
+
+    #[test]
+    fn should_evaluate_arithmetic_with_widening() {
+        // Given a row with a quantity, a price and a large counter
+        let row = test_record(
+            IntegerEncoding::Auto,
+            vec![
+                (1, 3i32.into()),
+                (2, 2.5f64.into()),
+                (3, (i64::MAX - 1).into()),
+                (4, Value::Null),
+            ],
+        );
+
+        // When evaluating arithmetic over them
+        // Then integers should widen to the wider type and floats should win
+        assert_eq!(eval("#1 * #2", &row).unwrap(), Value::Float64(7.5));
+        assert_eq!(eval("#1 + 1", &row).unwrap(), Value::Int32(4));
+        assert_eq!(eval("-#1 * (2 + 1) % 5", &row).unwrap(), Value::Int32(-4));
+        assert_eq!(eval("#3 - #1", &row).unwrap(), Value::Int64(i64::MAX - 4));
+        assert_eq!(eval("#1 / 2", &row).unwrap(), Value::Int32(1));
+        assert_eq!(eval("-2147483648", &row).unwrap(), Value::Int32(i32::MIN));
+        assert_eq!(eval("#4 + 1", &row).unwrap(), Value::Null);
+        assert_eq!(eval("#9 * 2", &row).unwrap(), Value::Null);
+
+        // And overflow, division by zero and non-numbers should fail
+        assert!(matches!(
+            eval("#3 + #1", &row),
+            Err(ImprintError::Expression(_))
+        ));
+        assert!(matches!(
+            eval("#1 / 0", &row),
+            Err(ImprintError::Expression(_))
+        ));
+        assert!(matches!(
+            eval("#1 + 'a'", &row),
+            Err(ImprintError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn should_evaluate_strings_casts_and_coalesce() {
+        // Given a fieldspace with named fields
+        let mut registry = SchemaRegistry::new();
+        registry.register_field_name(1, 1, "first_name");
+        registry.register_field_name(1, 2, "last_name");
+        registry.register_field_name(1, 3, "nickname");
+        registry.register_field_name(1, 4, "age");
+        let row = test_record(
+            IntegerEncoding::Auto,
+            vec![
+                (1, Value::from("  Ada ")),
+                (2, Value::from("Lovelace")),
+                (3, Value::Null),
+                (4, 36i32.into()),
+            ],
+        );
+        let eval = |source: &str| {
+            Expr::parse_with_names(source, &registry, 1)
+                .unwrap()
+                .eval(&row)
+                .unwrap()
+        };
+
+        // When evaluating string functions, casts and coalesce over them
+        // Then they should compute the derived values
+        assert_eq!(
+            eval("coalesce(nickname, trim(first_name) || ' ' || upper(last_name))"),
+            Value::from("Ada LOVELACE")
+        );
+        assert_eq!(
+            eval("concat(lower(last_name), '-', cast(age as string))"),
+            Value::from("lovelace-36")
+        );
+        assert_eq!(eval("length(first_name)"), Value::Int64(6));
+        assert_eq!(eval("substr(last_name, 2, 4)"), Value::from("ovel"));
+        assert_eq!(eval("substr(last_name, 0, 2)"), Value::from("L"));
+        assert_eq!(eval("cast(age as int64) * 2"), Value::Int64(72));
+        assert_eq!(eval("cast('2.5' as float64) + age"), Value::Float64(38.5));
+        assert_eq!(eval("cast(-1.9 as int8)"), Value::Int8(-1));
+        assert_eq!(eval("cast(nickname as int64)"), Value::Null);
+        assert_eq!(eval("upper(nickname)"), Value::Null);
+
+        // And out of range casts and unknown names should fail
+        assert!(
+            Expr::parse_with_names("cast(300 as uint8)", &registry, 1)
+                .unwrap()
+                .eval(&row)
+                .is_err()
+        );
+        assert!(Expr::parse_with_names("middle_name", &registry, 1).is_err());
+        assert!(Expr::parse_with_names("first_name", &registry, 2).is_err());
+    }
+
+    #[test]
+    fn should_follow_paths_into_nested_values() {
+        // Given a row with a nested row, a map and an array
+        let inner = test_record(IntegerEncoding::Auto, vec![(2, Value::from("inner"))]);
+        let map: HashMap<MapKey, Value> = [
+            (MapKey::from("city"), Value::from("Paris")),
+            (MapKey::from("zip"), Value::from("75001")),
+        ]
+        .into();
+        let ids: HashMap<MapKey, Value> = [(MapKey::Int64(7), Value::from("seven"))].into();
+        let row = test_record(
+            IntegerEncoding::Auto,
+            vec![
+                (1, Value::Row(Box::new(inner))),
+                (2, Value::Map(map)),
+                (3, Value::Array(vec![10.into(), 20.into()])),
+                (4, Value::Map(ids)),
+                (5, Value::Union(Box::new(Value::Array(vec![1.into()])))),
+            ],
+        );
+
+        // When following paths into them
+        // Then they should reach the nested values, or null where there are none
+        assert_eq!(eval("#1.#2", &row).unwrap(), Value::from("inner"));
+        assert_eq!(eval("#1[1 + 1]", &row).unwrap(), Value::from("inner"));
+        assert_eq!(eval("#2.city", &row).unwrap(), Value::from("Paris"));
+        assert_eq!(
+            eval("#2['zip'] || '!'", &row).unwrap(),
+            Value::from("75001!")
+        );
+        assert_eq!(eval("#3[1] + #3[0]", &row).unwrap(), Value::Int32(30));
+        assert_eq!(eval("#4[7]", &row).unwrap(), Value::from("seven"));
+        assert_eq!(eval("#5[0]", &row).unwrap(), Value::Int32(1));
+        assert_eq!(eval("#3[5]", &row).unwrap(), Value::Null);
+        assert_eq!(eval("#2.country", &row).unwrap(), Value::Null);
+        assert_eq!(eval("#9.a.b", &row).unwrap(), Value::Null);
+        assert!(eval("#3.#1[0]", &row).is_err());
+    }
+
+    #[test]
+    fn should_reject_malformed_expressions() {
+        for source in [
+            "",
+            "#1 +",
+            "(#1",
+            "#1 #2",
+            "foo(#1)",
+            "upper(#1, #2)",
+            "cast(#1 int64)",
+            "cast(#1 as blob)",
+            "'open",
+            "#1 $ 2",
+            "#x",
+            "#1.",
+            "99999999999999999999999",
+        ] {
+            assert!(
+                matches!(Expr::parse(source), Err(ImprintError::Expression(_))),
+                "{:?} should not parse",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn should_bound_nesting_depth() {
+        // Given expressions nested just within and far beyond the limit
+        let parens = |depth| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        let within = parens(MAX_NESTING - 1);
+
+        // Then those within the limit should parse
+        assert!(Expr::parse(&within).is_ok());
+        assert!(Expr::parse(&format!("{}#1", "-".repeat(MAX_NESTING - 1))).is_ok());
+        assert!(Expr::parse(&format!("{}1", "1+".repeat(MAX_NESTING - 1))).is_ok());
+
+        // And deeper ones should fail instead of overflowing the stack
+        for source in [
+            parens(100_000),
+            format!("{}1", "-".repeat(100_000)),
+            format!("{}1{}", "upper(".repeat(100_000), ")".repeat(100_000)),
+            format!("#1{}", "[#1".repeat(100_000)),
+            format!("{}1", "1+".repeat(100_000)),
+            format!("{}1", "1*".repeat(100_000)),
+            format!("{}'a'", "'a'||".repeat(100_000)),
+        ] {
+            assert!(matches!(
+                Expr::parse(&source),
+                Err(ImprintError::Expression(_))
+            ));
+        }
+    }
+}
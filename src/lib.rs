@@ -1,18 +1,37 @@
+mod canonical;
+mod compression;
+mod decimal;
 mod error;
+mod expr;
 mod ops;
+mod order;
+mod patch;
+mod predicate;
+mod schema;
 mod serde;
+mod sort_key;
+mod temporal;
 mod types;
-mod varint;
+pub mod varint;
 mod writer;
 
+pub use compression::Compression;
+pub use decimal::Decimal;
 pub use error::ImprintError;
-pub use ops::{Merge, MergeOptions, Project};
+pub use expr::{BinaryOp, Expr, Function};
+pub use ops::{ArrayMerge, Compact, Merge, MergeOptions, Project, Remap, Select, Update};
+pub use order::{OrderedValue, cmp_raw};
+pub use patch::{RecordDiff, apply_patch, diff};
+pub use predicate::{CompiledPredicate, Predicate};
+pub use schema::{Schema, SchemaRegistry};
 pub use serde::{Read, Write};
+pub use sort_key::{NullOrder, SortOrder, decode_sort_key};
+pub use temporal::{TimeUnit, Timestamp};
 pub use types::{
     DirectoryEntry, Flags, Header, ImprintRecord, MAGIC, SchemaId, TypeCode, VERSION, Value,
 };
 pub use varint::{decode as decode_varint, encode as encode_varint};
-pub use writer::ImprintWriter;
+pub use writer::{ImprintWriter, IntegerEncoding};
 
 /// Result type for Imprint operations
 pub type Result<T> = std::result::Result<T, error::ImprintError>;
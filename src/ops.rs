@@ -1,8 +1,59 @@
 use crate::{
     error::ImprintError,
-    types::{DirectoryEntry, Header, ImprintRecord, SchemaId},
+    expr::Expr,
+    schema::{SchemaRegistry, derived_schema_id},
+    serde::{ValueRead, Write},
+    types::{DirectoryEntry, Flags, Header, ImprintRecord, Payload, SchemaId, TypeCode, Value},
+    varint,
 };
 use bytes::BytesMut;
+use std::{cmp::Ordering, collections::HashMap};
+
+/// Copy the values of the given directory entries into a new payload, laid out in
+/// the order given, returning the new directory and payload. We do all this
+/// shenanigans with the ranges to avoid allocating a new payload buffer until we
+/// know the final size (zero copy makes a significant difference here), and values
+/// that are adjacent in the source are copied as a single range.
+fn copy_fields<'a>(
+    payload: &[u8],
+    entries: impl IntoIterator<Item = &'a DirectoryEntry>,
+) -> Result<(Vec<DirectoryEntry>, BytesMut), ImprintError> {
+    let mut new_directory = Vec::new();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut current_offset = 0;
+
+    for entry in entries {
+        let start = entry.offset as usize;
+        let end = start + entry.length as usize;
+        if end > payload.len() {
+            return Err(ImprintError::BufferUnderflow {
+                needed: end,
+                available: payload.len(),
+            });
+        }
+
+        new_directory.push(DirectoryEntry {
+            id: entry.id,
+            type_code: entry.type_code,
+            offset: varint::checked_u32(current_offset, "field offset")?,
+            length: entry.length,
+        });
+
+        match ranges.last_mut() {
+            Some(last) if last.1 == start => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+        // entries may overlap, so their lengths can add up past the source payload
+        current_offset += entry.length as usize;
+    }
+
+    varint::checked_u32(current_offset, "payload size")?;
+    let mut new_payload = BytesMut::with_capacity(current_offset);
+    for (start, end) in ranges {
+        new_payload.extend_from_slice(&payload[start..end]);
+    }
+    Ok((new_directory, new_payload))
+}
 
 pub trait Project {
     fn project(&self, field_ids: &[u32]) -> Result<ImprintRecord, ImprintError>;
@@ -14,62 +65,345 @@ impl Project for ImprintRecord {
         sorted_field_ids.sort_unstable();
         sorted_field_ids.dedup();
 
-        // we do all this shenanigans with the ranges to avoid allocating a new
-        // payload buffer until we know the final size (zero copy makes a significant
-        // difference here)
-        let mut new_directory = Vec::with_capacity(sorted_field_ids.len());
-        let mut ranges = Vec::new();
-
-        // iterate through the directory fields and compute ranges to copy over
+        // iterate through the directory fields and pick the ones to copy over
+        let mut selected = Vec::with_capacity(sorted_field_ids.len());
         let mut field_ids_idx = 0;
         let mut directory_idx = 0;
-        let mut current_offset = 0;
 
         while directory_idx < self.directory.len() && field_ids_idx < sorted_field_ids.len() {
             let field = &self.directory[directory_idx];
 
-            if field.id == sorted_field_ids[field_ids_idx] {
-                // Get the next field's offset to determine this field's length
-                // we can't just use get_raw_bytes here because the field may
-                // start with a length prefix
-                let next_offset = if directory_idx + 1 < self.directory.len() {
-                    self.directory[directory_idx + 1].offset
-                } else {
-                    self.payload.len() as u32
-                };
+            match field.id.cmp(&sorted_field_ids[field_ids_idx]) {
+                Ordering::Less => directory_idx += 1,
+                Ordering::Greater => field_ids_idx += 1,
+                Ordering::Equal => {
+                    selected.push(field);
+                    directory_idx += 1;
+                    field_ids_idx += 1;
+                }
+            }
+        }
 
-                let field_length = next_offset - field.offset;
+        let (new_directory, new_payload) = copy_fields(self.payload.get()?, selected)?;
 
-                new_directory.push(DirectoryEntry {
-                    id: field.id,
-                    type_code: field.type_code,
-                    offset: current_offset,
-                });
+        Ok(ImprintRecord {
+            header: Header {
+                // the result no longer matches a fixed schema, so it needs a directory
+                flags: self.header.flags.with(Flags::FIELD_DIRECTORY, true),
+                schema_id: SchemaId {
+                    fieldspace_id: self.header.schema_id.fieldspace_id,
+                    schema_hash: 0xdeadbeef, // TODO: compute the correct schema hash
+                },
+                payload_size: varint::checked_u32(new_payload.len(), "payload size")?,
+            },
+            directory: new_directory,
+            payload: Payload::new(new_payload.freeze(), self.payload.compression()),
+        })
+    }
+}
+
+pub trait Select {
+    /// Build a record whose fields are the given expressions evaluated against
+    /// this one, keyed by their output field IDs. Expressions that are a plain
+    /// field reference copy the field's bytes without decoding them, and are
+    /// left out if the field is missing or deleted by a tombstone.
+    fn select(&self, outputs: &[(u32, Expr)]) -> Result<ImprintRecord, ImprintError>;
+}
+
+impl Select for ImprintRecord {
+    fn select(&self, outputs: &[(u32, Expr)]) -> Result<ImprintRecord, ImprintError> {
+        let mut outputs: Vec<_> = outputs.iter().collect();
+        outputs.sort_unstable_by_key(|(id, _)| *id);
+        if let Some(w) = outputs.windows(2).find(|w| w[0].0 == w[1].0) {
+            return Err(ImprintError::SchemaError(format!(
+                "duplicate output field id: {}",
+                w[0].0
+            )));
+        }
+
+        let mut new_directory = Vec::with_capacity(outputs.len());
+        let mut new_payload = BytesMut::new();
+        for (id, expr) in outputs {
+            let offset = varint::checked_u32(new_payload.len(), "field offset")?;
+            let type_code = match expr {
+                Expr::Field(field_id) => match self.entry(*field_id) {
+                    Some(entry) if entry.type_code != TypeCode::Tombstone => {
+                        new_payload.extend_from_slice(&self.entry_bytes(entry)?);
+                        entry.type_code
+                    }
+                    _ => continue,
+                },
+                expr => {
+                    let value = expr.eval(self)?;
+                    value.write(&mut new_payload)?;
+                    value.type_code()
+                }
+            };
+            new_directory.push(DirectoryEntry {
+                id: *id,
+                type_code,
+                offset,
+                length: varint::checked_u32(new_payload.len(), "payload size")? - offset,
+            });
+        }
+
+        Ok(ImprintRecord {
+            header: Header {
+                flags: self.header.flags.with(Flags::FIELD_DIRECTORY, true),
+                schema_id: derived_schema_id(self.header.schema_id.fieldspace_id, &new_directory),
+                payload_size: varint::checked_u32(new_payload.len(), "payload size")?,
+            },
+            directory: new_directory,
+            payload: Payload::new(new_payload.freeze(), self.payload.compression()),
+        })
+    }
+}
+
+pub trait Remap {
+    /// Renumber fields from their old to their new IDs, keeping the IDs of fields
+    /// missing from the mapping. Only the directory is rewritten and re-sorted, the
+    /// payload is shared untouched.
+    fn remap(&self, mapping: &HashMap<u32, u32>) -> Result<ImprintRecord, ImprintError>;
+
+    /// Move the record into another fieldspace, renumbering each field to the ID
+    /// registered under the same name there. Every field must have a name in both.
+    fn translate(
+        &self,
+        registry: &SchemaRegistry,
+        fieldspace_id: u32,
+    ) -> Result<ImprintRecord, ImprintError>;
+}
+
+/// Give every field of the record the ID returned by `new_id` and move it into
+/// the given fieldspace, sharing the payload with the original record.
+fn renumber_fields(
+    record: &ImprintRecord,
+    fieldspace_id: u32,
+    new_id: impl Fn(u32) -> Result<u32, ImprintError>,
+) -> Result<ImprintRecord, ImprintError> {
+    let mut new_directory = record
+        .directory
+        .iter()
+        .map(|entry| {
+            Ok(DirectoryEntry {
+                id: new_id(entry.id)?,
+                ..*entry
+            })
+        })
+        .collect::<Result<Vec<_>, ImprintError>>()?;
+    new_directory.sort_unstable_by_key(|e| e.id);
+    if let Some(w) = new_directory.windows(2).find(|w| w[0].id == w[1].id) {
+        return Err(ImprintError::SchemaError(format!(
+            "more than one field remapped to id {}",
+            w[0].id
+        )));
+    }
+
+    // the record keeps its schema if no field was renumbered
+    let schema_id = if fieldspace_id == record.header.schema_id.fieldspace_id
+        && new_directory == record.directory
+    {
+        record.header.schema_id
+    } else {
+        derived_schema_id(fieldspace_id, &new_directory)
+    };
+
+    Ok(ImprintRecord {
+        header: Header {
+            flags: record.header.flags.with(Flags::FIELD_DIRECTORY, true),
+            schema_id,
+            payload_size: record.header.payload_size,
+        },
+        directory: new_directory,
+        payload: record.payload.clone(),
+    })
+}
+
+impl Remap for ImprintRecord {
+    fn remap(&self, mapping: &HashMap<u32, u32>) -> Result<ImprintRecord, ImprintError> {
+        renumber_fields(self, self.header.schema_id.fieldspace_id, |id| {
+            Ok(mapping.get(&id).copied().unwrap_or(id))
+        })
+    }
+
+    fn translate(
+        &self,
+        registry: &SchemaRegistry,
+        fieldspace_id: u32,
+    ) -> Result<ImprintRecord, ImprintError> {
+        let source = self.header.schema_id.fieldspace_id;
+        renumber_fields(self, fieldspace_id, |id| {
+            let name = registry.field_name(source, id).ok_or_else(|| {
+                ImprintError::SchemaError(format!("field {id} has no name in fieldspace {source}"))
+            })?;
+            registry.field_id(fieldspace_id, name).ok_or_else(|| {
+                ImprintError::SchemaError(format!(
+                    "field {name} does not exist in fieldspace {fieldspace_id}"
+                ))
+            })
+        })
+    }
+}
+
+pub trait Update {
+    /// Set a field to the given value, inserting it if it is missing.
+    fn with_field(&self, field_id: u32, value: Value) -> Result<ImprintRecord, ImprintError> {
+        self.with_updates(vec![(field_id, Some(value))])
+    }
+
+    /// Remove a field, if present.
+    fn without_field(&self, field_id: u32) -> Result<ImprintRecord, ImprintError> {
+        self.with_updates(vec![(field_id, None)])
+    }
+
+    /// Apply many updates in one pass, where `None` removes the field. Only the new
+    /// values are encoded and the bytes of the other fields are copied over as is.
+    /// When a field is updated more than once the last update wins.
+    fn with_updates(
+        &self,
+        updates: Vec<(u32, Option<Value>)>,
+    ) -> Result<ImprintRecord, ImprintError>;
+}
 
-                ranges.push((field.offset, next_offset));
-                current_offset += field_length;
-                field_ids_idx += 1;
+/// Apply updates to the fields of a record in one pass, where `None` removes the
+/// field, returning the new directory and payload. New values are encoded with
+/// `write`, which returns their type code, and the bytes of every other field are
+/// copied over. When a field is updated more than once the last update wins.
+pub(crate) fn splice_fields<T>(
+    record: &ImprintRecord,
+    mut updates: Vec<(u32, Option<T>)>,
+    write: impl Fn(T, &mut BytesMut) -> Result<TypeCode, ImprintError>,
+) -> Result<(Vec<DirectoryEntry>, BytesMut), ImprintError> {
+    // a stable sort keeps updates to the same field in order, so keeping the last
+    // of each run applies them in order
+    updates.sort_by_key(|(id, _)| *id);
+    let mut updates = updates.into_iter().peekable();
+
+    let payload = record.payload.get()?;
+    let mut new_directory = Vec::with_capacity(record.directory.len() + updates.len());
+    let mut new_payload = BytesMut::with_capacity(payload.len());
+    // bytes of unchanged fields adjacent in the source are copied as one range
+    let mut pending: Option<(usize, usize)> = None;
+    let mut directory_idx = 0;
+
+    loop {
+        let entry = record.directory.get(directory_idx);
+        let update_id = updates.peek().map(|(id, _)| *id);
+        if let Some(id) = update_id.filter(|id| entry.is_none_or(|entry| *id <= entry.id)) {
+            let (_, mut value) = updates.next().unwrap();
+            while updates.peek().is_some_and(|(next, _)| *next == id) {
+                value = updates.next().unwrap().1;
             }
+            if entry.is_some_and(|entry| entry.id == id) {
+                directory_idx += 1;
+            }
+            let Some(value) = value else { continue };
 
+            if let Some((start, end)) = pending.take() {
+                new_payload.extend_from_slice(&payload[start..end]);
+            }
+            let offset = varint::checked_u32(new_payload.len(), "field offset")?;
+            let type_code = write(value, &mut new_payload)?;
+            new_directory.push(DirectoryEntry {
+                id,
+                type_code,
+                offset,
+                length: varint::checked_u32(new_payload.len(), "payload size")? - offset,
+            });
+        } else if let Some(entry) = entry {
+            let start = entry.offset as usize;
+            let end = start + entry.length as usize;
+            if end > payload.len() {
+                return Err(ImprintError::BufferUnderflow {
+                    needed: end,
+                    available: payload.len(),
+                });
+            }
+            let offset = new_payload.len() + pending.map_or(0, |(start, end)| end - start);
+            new_directory.push(DirectoryEntry {
+                offset: varint::checked_u32(offset, "field offset")?,
+                ..*entry
+            });
+            pending = match pending {
+                Some((pending_start, pending_end)) if pending_end == start => {
+                    Some((pending_start, end))
+                }
+                Some((pending_start, pending_end)) => {
+                    new_payload.extend_from_slice(&payload[pending_start..pending_end]);
+                    Some((start, end))
+                }
+                None => Some((start, end)),
+            };
             directory_idx += 1;
+        } else {
+            break;
         }
+    }
+    if let Some((start, end)) = pending {
+        new_payload.extend_from_slice(&payload[start..end]);
+    }
+    Ok((new_directory, new_payload))
+}
+
+impl Update for ImprintRecord {
+    fn with_updates(
+        &self,
+        updates: Vec<(u32, Option<Value>)>,
+    ) -> Result<ImprintRecord, ImprintError> {
+        let (new_directory, new_payload) = splice_fields(self, updates, |value, buf| {
+            value.write(buf)?;
+            Ok(value.type_code())
+        })?;
+
+        // the record still matches its schema if the fields and their types are unchanged
+        let same_shape = new_directory.len() == self.directory.len()
+            && new_directory
+                .iter()
+                .zip(&self.directory)
+                .all(|(new, old)| new.id == old.id && new.type_code == old.type_code);
+        let (flags, schema_id) = if same_shape {
+            (self.header.flags, self.header.schema_id)
+        } else {
+            (
+                self.header.flags.with(Flags::FIELD_DIRECTORY, true),
+                derived_schema_id(self.header.schema_id.fieldspace_id, &new_directory),
+            )
+        };
+
+        Ok(ImprintRecord {
+            header: Header {
+                flags,
+                schema_id,
+                payload_size: varint::checked_u32(new_payload.len(), "payload size")?,
+            },
+            directory: new_directory,
+            payload: Payload::new(new_payload.freeze(), self.payload.compression()),
+        })
+    }
+}
+
+pub trait Compact {
+    /// Rewrite the payload so that it only contains the bytes referenced by the
+    /// directory, dropping any zombie data left behind by merges.
+    fn compact(&self) -> Result<ImprintRecord, ImprintError>;
+}
 
-        let mut new_payload = BytesMut::with_capacity(current_offset as usize);
-        for range in ranges {
-            new_payload.extend_from_slice(&self.payload[range.0 as usize..range.1 as usize]);
+impl Compact for ImprintRecord {
+    fn compact(&self) -> Result<ImprintRecord, ImprintError> {
+        if self.wasted_bytes() == 0 {
+            return Ok(self.clone());
         }
 
+        let (new_directory, new_payload) = copy_fields(self.payload.get()?, &self.directory)?;
+
         Ok(ImprintRecord {
             header: Header {
                 flags: self.header.flags,
-                schema_id: SchemaId {
-                    fieldspace_id: self.header.schema_id.fieldspace_id,
-                    schema_hash: 0xdeadbeef, // TODO: compute the correct schema hash
-                },
-                payload_size: new_payload.len() as u32,
+                schema_id: self.header.schema_id,
+                payload_size: varint::checked_u32(new_payload.len(), "payload size")?,
             },
             directory: new_directory,
-            payload: new_payload.freeze(),
+            payload: Payload::new(new_payload.freeze(), self.payload.compression()),
         })
     }
 }
@@ -79,12 +413,139 @@ pub struct MergeOptions {
     /// If true, duplicate fields from the second record will be filtered out of the payload
     /// If false, they will remain in the payload but won't be accessible via the directory
     pub filter_duplicate_payloads: bool,
+    /// If true, fields present in both records must have the same logical type,
+    /// otherwise the merge fails. A union field is only compatible with another
+    /// union field, whichever branches they hold, since the branch is part of
+    /// the value rather than the field's type.
+    pub check_conflicts: bool,
+    /// If true, tombstones that win the merge are kept in the result so that they
+    /// also delete the field in later merges. If false, the field is left out.
+    pub keep_tombstones: bool,
+    /// If true, fields present in both records that are both rows, maps or arrays
+    /// are merged rather than the first one winning. Rows are merged field by field
+    /// and maps key by key with these same options, and arrays as set by `arrays`.
+    pub deep: bool,
+    /// How arrays present in both records are merged when merging deeply
+    pub arrays: ArrayMerge,
+}
+
+/// How a deep merge combines two arrays
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArrayMerge {
+    /// Keep the array of the first record
+    #[default]
+    Replace,
+    /// Append the elements of the second array to those of the first
+    Concat,
+}
+
+/// Check that a field present in both merged records has the same logical type in
+/// each, unless either deletes it
+fn check_conflict(first: &DirectoryEntry, second: &DirectoryEntry) -> Result<(), ImprintError> {
+    let deleted = first.type_code == TypeCode::Tombstone || second.type_code == TypeCode::Tombstone;
+    if !deleted && first.type_code.logical() != second.type_code.logical() {
+        return Err(ImprintError::MergeConflict {
+            field_id: first.id,
+            first: first.type_code,
+            second: second.type_code,
+        });
+    }
+    Ok(())
+}
+
+/// Merge the values of a field present in both records if they are rows, maps or
+/// arrays that a deep merge combines, returning `None` if the first one wins
+fn deep_merge(
+    first: &ImprintRecord,
+    first_entry: &DirectoryEntry,
+    second: &ImprintRecord,
+    second_entry: &DirectoryEntry,
+    options: MergeOptions,
+) -> Result<Option<Value>, ImprintError> {
+    let type_code = first_entry.type_code;
+    let combined = match type_code {
+        TypeCode::Row | TypeCode::Map => true,
+        TypeCode::Array => options.arrays == ArrayMerge::Concat,
+        _ => false,
+    };
+    if !combined || second_entry.type_code != type_code {
+        return Ok(None);
+    }
+
+    let (first_value, _) = Value::read(type_code, first.entry_bytes(first_entry)?)?;
+    let (second_value, _) = Value::read(type_code, second.entry_bytes(second_entry)?)?;
+    merge_values(first_entry.id, first_value, second_value, options).map(Some)
+}
+
+/// Deeply merge two values of the given field, with the first winning whatever
+/// can't be combined
+fn merge_values(
+    field_id: u32,
+    first: Value,
+    second: Value,
+    options: MergeOptions,
+) -> Result<Value, ImprintError> {
+    match (first, second) {
+        // the fields of nested rows are still merged as raw bytes
+        (Value::Row(first), Value::Row(second)) => Ok(Value::Row(Box::new(
+            first.merge_with_opts(&second, options)?,
+        ))),
+        (Value::Map(mut first), Value::Map(second)) => {
+            // a map has a single key type and value type, taken from any of its entries
+            let types = first
+                .iter()
+                .next()
+                .map(|(key, value)| (key.type_code(), value.type_code()));
+            for (key, value) in second {
+                let merged = match first.remove(&key) {
+                    Some(existing) => {
+                        if options.check_conflicts && existing.type_code() != value.type_code() {
+                            return Err(ImprintError::MergeConflict {
+                                field_id,
+                                first: existing.type_code(),
+                                second: value.type_code(),
+                            });
+                        }
+                        merge_values(field_id, existing, value, options)?
+                    }
+                    None => match types {
+                        Some((key_type, value_type))
+                            if (key_type, value_type) != (key.type_code(), value.type_code()) =>
+                        {
+                            if options.check_conflicts {
+                                let (first, second) = if key_type != key.type_code() {
+                                    (key_type, key.type_code())
+                                } else {
+                                    (value_type, value.type_code())
+                                };
+                                return Err(ImprintError::MergeConflict {
+                                    field_id,
+                                    first,
+                                    second,
+                                });
+                            }
+                            continue;
+                        }
+                        _ => value,
+                    },
+                };
+                first.insert(key, merged);
+            }
+            Ok(Value::Map(first))
+        }
+        (Value::Array(mut first), Value::Array(second)) if options.arrays == ArrayMerge::Concat => {
+            first.extend(second);
+            Ok(Value::Array(first))
+        }
+        (first, _) => Ok(first),
+    }
 }
 
 pub trait Merge {
     /// Merge another record into this one, using default options.
     /// By default, duplicate fields from the second record will be kept in the payload
-    /// but won't be accessible via the directory.
+    /// but won't be accessible via the directory, and fields whose winning value is a
+    /// [`Value::Tombstone`] are removed from the result.
     fn merge(&self, other: &ImprintRecord) -> Result<ImprintRecord, ImprintError> {
         self.merge_with_opts(other, MergeOptions::default())
     }
@@ -106,17 +567,23 @@ impl Merge for ImprintRecord {
         // we just shrink the directory and payload to the exact size we need at the end of the
         // merge and allocate the largest possible sizes up front assuming that the records do
         // not have significant overlaping fields
+        let self_payload = self.payload.get()?;
+        let other_payload = other.payload.get()?;
         let mut new_directory = Vec::with_capacity(self.directory.len() + other.directory.len());
-        let mut new_payload = BytesMut::with_capacity(self.payload.len() + other.payload.len());
+        let mut new_payload = BytesMut::with_capacity(self_payload.len() + other_payload.len());
 
+        // checking the combined size up front ensures no offset below can overflow
+        varint::checked_u32(self_payload.len() + other_payload.len(), "payload size")?;
         new_directory.extend_from_slice(&self.directory);
-        new_payload.extend_from_slice(&self.payload);
+        new_payload.extend_from_slice(self_payload);
 
-        let base_offset = new_payload.len() as u32;
+        let base_offset = self_payload.len() as u32;
+        // values of fields present in both records that were merged deeply, by their
+        // index in the first record's directory
+        let mut merged_fields = Vec::new();
 
         if options.filter_duplicate_payloads {
             // If filtering duplicates, we need to process each field individually
-            let mut current_offset = 0u32;
             let mut self_idx = 0;
 
             for entry in &other.directory {
@@ -127,26 +594,35 @@ impl Merge for ImprintRecord {
 
                 // If we found a match, skip this field
                 if self_idx < self.directory.len() && self.directory[self_idx].id == entry.id {
+                    if options.check_conflicts {
+                        check_conflict(&self.directory[self_idx], entry)?;
+                    }
+                    if options.deep {
+                        let first = &self.directory[self_idx];
+                        if let Some(merged) = deep_merge(self, first, other, entry, options)? {
+                            merged_fields.push((self_idx, merged));
+                        }
+                    }
                     continue;
                 }
 
-                let field_bytes = other.get_raw_bytes(entry.id).unwrap();
+                let field_bytes = other.get_raw_bytes(entry.id)?.unwrap();
 
                 // Add adjusted directory entry
                 let new_entry = DirectoryEntry {
                     id: entry.id,
                     type_code: entry.type_code,
-                    offset: base_offset + current_offset,
+                    offset: varint::checked_u32(new_payload.len(), "field offset")?,
+                    length: entry.length,
                 };
                 new_directory.push(new_entry);
 
                 // Copy corresponding payload
                 new_payload.extend_from_slice(field_bytes.as_ref());
-                current_offset += field_bytes.len() as u32;
             }
         } else {
             // If not filtering duplicates, we can just append the entire payload
-            new_payload.extend_from_slice(&other.payload);
+            new_payload.extend_from_slice(other_payload);
 
             // Add all non-duplicate directory entries with adjusted offsets
             let mut self_idx = 0;
@@ -159,6 +635,15 @@ impl Merge for ImprintRecord {
 
                 // If we found a match, skip this field
                 if self_idx < self.directory.len() && self.directory[self_idx].id == entry.id {
+                    if options.check_conflicts {
+                        check_conflict(&self.directory[self_idx], entry)?;
+                    }
+                    if options.deep {
+                        let first = &self.directory[self_idx];
+                        if let Some(merged) = deep_merge(self, first, other, entry, options)? {
+                            merged_fields.push((self_idx, merged));
+                        }
+                    }
                     continue;
                 }
 
@@ -166,25 +651,45 @@ impl Merge for ImprintRecord {
                     id: entry.id,
                     type_code: entry.type_code,
                     offset: base_offset + entry.offset,
+                    length: entry.length,
                 };
                 new_directory.push(new_entry);
             }
         }
 
+        // Deeply merged values are appended and replace the first record's entries,
+        // which are still at the front of the directory at their original indices
+        for (idx, value) in merged_fields {
+            let offset = varint::checked_u32(new_payload.len(), "field offset")?;
+            value.write(&mut new_payload)?;
+            new_directory[idx] = DirectoryEntry {
+                id: new_directory[idx].id,
+                type_code: value.type_code(),
+                offset,
+                length: varint::checked_u32(new_payload.len(), "payload size")? - offset,
+            };
+        }
+
         // Sort directory by field ID to maintain canonical form
         new_directory.sort_by_key(|e| e.id);
 
+        // The first record wins duplicates, so a tombstone left here deletes the field
+        if !options.keep_tombstones {
+            new_directory.retain(|e| e.type_code != TypeCode::Tombstone);
+        }
+
         // Shrink allocations to fit actual data
         new_directory.shrink_to_fit();
 
         Ok(ImprintRecord {
             header: Header {
-                flags: self.header.flags,
+                // the result no longer matches a fixed schema, so it needs a directory
+                flags: self.header.flags.with(Flags::FIELD_DIRECTORY, true),
                 schema_id: self.header.schema_id,
-                payload_size: new_payload.len() as u32,
+                payload_size: varint::checked_u32(new_payload.len(), "payload size")?,
             },
             directory: new_directory,
-            payload: new_payload.freeze(),
+            payload: Payload::new(new_payload.freeze(), self.payload.compression()),
         })
     }
 }
@@ -192,7 +697,9 @@ impl Merge for ImprintRecord {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ImprintWriter;
+    use crate::writer::test_record;
+    use crate::{ImprintWriter, IntegerEncoding, Read, TypeCode, Value, Write};
+    use bytes::Bytes;
 
     fn create_test_record() -> ImprintRecord {
         let mut writer = ImprintWriter::new(SchemaId {
@@ -294,7 +801,7 @@ mod tests {
 
         // Then result should be empty but valid
         assert_eq!(projected.directory.len(), 0);
-        assert!(projected.payload.is_empty());
+        assert!(projected.payload.get().unwrap().is_empty());
     }
 
     #[test]
@@ -340,20 +847,20 @@ mod tests {
 
         // Then result should be empty but valid
         assert_eq!(projected.directory.len(), 0);
-        assert!(projected.payload.is_empty());
+        assert!(projected.payload.get().unwrap().is_empty());
     }
 
     #[test]
     fn should_preserve_exact_byte_representation() {
         // Given a record with multiple fields
         let record = create_test_record();
-        let original_bytes = record.get_raw_bytes(3).unwrap();
+        let original_bytes = record.get_raw_bytes(3).unwrap().unwrap();
 
         // When projecting a field
         let projected = record.project(&[3]).unwrap();
 
         // Then the byte representation should be exactly preserved
-        let projected_bytes = projected.get_raw_bytes(3).unwrap();
+        let projected_bytes = projected.get_raw_bytes(3).unwrap().unwrap();
         assert_eq!(
             original_bytes, projected_bytes,
             "byte representation should be identical"
@@ -471,6 +978,7 @@ mod tests {
                 &record2,
                 MergeOptions {
                     filter_duplicate_payloads: true,
+                    ..Default::default()
                 },
             )
             .unwrap();
@@ -502,4 +1010,737 @@ mod tests {
         // Then schema ID from first record should be preserved
         assert_eq!(merged.header.schema_id, schema1);
     }
+
+    #[test]
+    fn should_recompute_checksum_after_project_and_merge() {
+        // Given two checksummed records
+        let mut writer1 = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xdeadbeef,
+        })
+        .unwrap();
+        writer1.set_checksum(true);
+        writer1.add_field(1, 42.into()).unwrap();
+        writer1.add_field(2, "first".into()).unwrap();
+        let record1 = writer1.build().unwrap();
+
+        let mut writer2 = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xcafebabe,
+        })
+        .unwrap();
+        writer2.set_checksum(true);
+        writer2.add_field(3, true.into()).unwrap();
+        let record2 = writer2.build().unwrap();
+
+        // When projecting and merging them
+        let merged = record1.project(&[2]).unwrap().merge(&record2).unwrap();
+
+        // Then the result should keep the checksum and roundtrip with a valid one
+        assert!(merged.has_checksum());
+        let mut buf = BytesMut::new();
+        merged.write(&mut buf).unwrap();
+        let (read, _) = ImprintRecord::read(buf.freeze()).unwrap();
+        assert_eq!(read.get_value(2).unwrap(), Some("first".into()));
+        assert_eq!(read.get_value(3).unwrap(), Some(true.into()));
+    }
+
+    #[test]
+    fn should_keep_exact_field_boundaries_after_merge_with_zombie_data() {
+        // Given two records with an overlapping field and interleaved ids
+        let mut writer1 = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xdeadbeef,
+        })
+        .unwrap();
+        writer1.add_field(1, "first".into()).unwrap();
+        writer1.add_field(3, 42.into()).unwrap();
+        let record1 = writer1.build().unwrap();
+
+        let mut writer2 = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xcafebabe,
+        })
+        .unwrap();
+        writer2.add_field(2, true.into()).unwrap();
+        writer2.add_field(3, 43.into()).unwrap();
+        writer2.add_field(4, "last".into()).unwrap();
+        let record2 = writer2.build().unwrap();
+
+        // When merging without filtering and roundtripping the result
+        let merged = record1.merge(&record2).unwrap();
+        let mut buf = BytesMut::new();
+        merged.write(&mut buf).unwrap();
+        let (read, _) = ImprintRecord::read(buf.freeze()).unwrap();
+
+        // Then the raw bytes of every field should be exact despite the zombie data
+        for record in [&merged, &read] {
+            assert_eq!(
+                record.get_raw_bytes(1).unwrap(),
+                record1.get_raw_bytes(1).unwrap()
+            );
+            assert_eq!(
+                record.get_raw_bytes(3).unwrap(),
+                record1.get_raw_bytes(3).unwrap()
+            );
+            assert_eq!(
+                record.get_raw_bytes(4).unwrap(),
+                record2.get_raw_bytes(4).unwrap()
+            );
+        }
+
+        // And projecting the merged record should not carry any zombie data
+        let projected = read.project(&[1, 3]).unwrap();
+        assert_eq!(projected, record1.project(&[1, 3]).unwrap());
+    }
+
+    #[test]
+    fn should_only_write_lengths_when_they_cannot_be_derived() {
+        // Given two records with distinct interleaved fields
+        let mut writer1 = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xdeadbeef,
+        })
+        .unwrap();
+        writer1.add_field(1, "first".into()).unwrap();
+        writer1.add_field(3, 42.into()).unwrap();
+        let record1 = writer1.build().unwrap();
+
+        let mut writer2 = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xcafebabe,
+        })
+        .unwrap();
+        writer2.add_field(2, true.into()).unwrap();
+        let record2 = writer2.build().unwrap();
+
+        // When merging them
+        let merged = record1.merge(&record2).unwrap();
+        let mut buf = BytesMut::new();
+        merged.write(&mut buf).unwrap();
+
+        // Then the lengths should be derived from the offsets rather than written
+        assert_eq!(buf[2] & Flags::FIELD_LENGTHS, 0);
+        let (read, _) = ImprintRecord::read(buf.freeze()).unwrap();
+        assert_eq!(read.directory, merged.directory);
+        assert_eq!(
+            read.get_raw_bytes(1).unwrap(),
+            record1.get_raw_bytes(1).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_reject_field_lengths_past_the_payload() {
+        // Given a record whose directory claims more bytes than its payload holds
+        let mut record = test_record(IntegerEncoding::Fixed, vec![(1, 42.into())]);
+        record.directory[0].length = 100;
+        let mut buf = BytesMut::new();
+        record.write(&mut buf).unwrap();
+
+        // Then reading it should fail
+        assert!(matches!(
+            ImprintRecord::read(buf.freeze()),
+            Err(ImprintError::BufferUnderflow { needed: 100, .. })
+        ));
+    }
+
+    #[test]
+    fn should_reject_projections_past_u32_payloads() {
+        // Given a record with thousands of entries overlapping a 1 MiB value
+        let mut record = test_record(IntegerEncoding::Fixed, vec![(0, vec![0u8; 1 << 20].into())]);
+        let entry = record.directory[0].clone();
+        record.directory = (0..5000)
+            .map(|id| DirectoryEntry {
+                id,
+                ..entry.clone()
+            })
+            .collect();
+
+        // Then projecting all of them should fail rather than wrap the offsets
+        let ids: Vec<u32> = (0..5000).collect();
+        assert!(matches!(
+            record.project(&ids),
+            Err(ImprintError::Overflow { .. })
+        ));
+    }
+
+    #[test]
+    fn should_project_fields_missing_between_existing_ones() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When projecting a missing field that sorts before an existing one
+        let projected = record.project(&[2, 3]).unwrap();
+
+        // Then the existing field should still be included
+        assert_eq!(projected.directory.len(), 1);
+        assert_eq!(projected.get_value(3).unwrap(), Some("hello".into()));
+    }
+
+    #[test]
+    fn should_compact_zombie_data_after_merge() {
+        // Given a merge of two records with overlapping fields that keeps zombie data
+        let mut writer1 = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xdeadbeef,
+        })
+        .unwrap();
+        writer1.add_field(1, 42.into()).unwrap();
+        writer1.add_field(2, "first".into()).unwrap();
+        let record1 = writer1.build().unwrap();
+
+        let mut writer2 = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xcafebabe,
+        })
+        .unwrap();
+        writer2.add_field(2, "second".into()).unwrap();
+        writer2.add_field(3, true.into()).unwrap();
+        let record2 = writer2.build().unwrap();
+
+        let merged = record1.merge(&record2).unwrap();
+        assert_eq!(merged.wasted_bytes(), "second".len() + 1);
+
+        // When compacting the merged record
+        let compacted = merged.compact().unwrap();
+
+        // Then no bytes should be wasted and the payload should match a filtered merge
+        let filtered = record1
+            .merge_with_opts(
+                &record2,
+                MergeOptions {
+                    filter_duplicate_payloads: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(compacted.wasted_bytes(), 0);
+        assert_eq!(compacted.payload.len(), filtered.payload.len());
+
+        // And every field should keep its value
+        for id in 1..=3 {
+            assert_eq!(
+                compacted.get_value(id).unwrap(),
+                merged.get_value(id).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn should_not_rewrite_record_without_waste() {
+        // Given a record without zombie data
+        let record = create_test_record();
+        assert_eq!(record.wasted_bytes(), 0);
+
+        // When compacting it
+        let compacted = record.compact().unwrap();
+
+        // Then it should be unchanged
+        assert_eq!(compacted, record);
+    }
+
+    #[test]
+    fn should_check_type_conflicts_when_merging() {
+        let record_with = |fields: Vec<(u32, Value)>, encoding: IntegerEncoding| {
+            let mut writer = ImprintWriter::new(SchemaId {
+                fieldspace_id: 1,
+                schema_hash: 0xdeadbeef,
+            })
+            .unwrap();
+            writer.set_integer_encoding(encoding);
+            for (id, value) in fields {
+                writer.add_field(id, value).unwrap();
+            }
+            writer.build().unwrap()
+        };
+        let options = MergeOptions {
+            check_conflicts: true,
+            ..Default::default()
+        };
+
+        // Given records whose shared fields have the same logical types, even though one
+        // int is written as a varint and the unions hold different branches
+        let first = record_with(
+            vec![
+                (1, 42i64.into()),
+                (2, Value::Union(Box::new("C-42".into()))),
+            ],
+            IntegerEncoding::Fixed,
+        );
+        let second = record_with(
+            vec![(1, 7i64.into()), (2, Value::Union(Box::new(42i64.into())))],
+            IntegerEncoding::Varint,
+        );
+
+        // When merging them with conflict checks
+        // Then the merge should succeed and keep the first record's values
+        let merged = first.merge_with_opts(&second, options).unwrap();
+        assert_eq!(merged.get_value(1).unwrap(), Some(42i64.into()));
+        assert_eq!(
+            merged.get_value(2).unwrap().unwrap().union_branch(),
+            Some(TypeCode::String)
+        );
+
+        // And when a shared field has a different type, or is a union on only one side
+        for conflicting in [
+            record_with(vec![(1, "42".into())], IntegerEncoding::Fixed),
+            record_with(vec![(2, "C-42".into())], IntegerEncoding::Fixed),
+        ] {
+            // Then the merge should fail with a conflict, but only when checking
+            assert!(matches!(
+                first.merge_with_opts(&conflicting, options),
+                Err(ImprintError::MergeConflict { .. })
+            ));
+            assert!(first.merge(&conflicting).is_ok());
+        }
+    }
+
+    #[test]
+    fn should_select_references_and_computed_fields() {
+        // Given a record with a varint quantity, a price and a name
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xdeadbeef,
+        })
+        .unwrap();
+        writer.set_integer_encoding(IntegerEncoding::Varint);
+        writer.add_field(1, 3i64.into()).unwrap();
+        writer.add_field(2, 2.5f64.into()).unwrap();
+        writer.add_field(3, "widget".into()).unwrap();
+        let record = writer.build().unwrap();
+
+        // When selecting a reference, a computed field and a missing reference
+        let outputs = [
+            (20, Expr::parse("#1 * #2").unwrap()),
+            (10, Expr::parse("#1").unwrap()),
+            (30, Expr::parse("upper(#3) || '!'").unwrap()),
+            (40, Expr::parse("#9").unwrap()),
+        ];
+        let selected = record.select(&outputs).unwrap();
+
+        // Then the reference should keep its encoding and bytes
+        let ids: Vec<u32> = selected.directory.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![10, 20, 30]);
+        assert_eq!(selected.directory[0].type_code, TypeCode::VarInt64);
+        assert_eq!(
+            selected.get_raw_bytes(10).unwrap(),
+            record.get_raw_bytes(1).unwrap()
+        );
+
+        // And the computed fields should hold their results
+        assert_eq!(selected.get_value(20).unwrap(), Some(7.5f64.into()));
+        assert_eq!(selected.get_value(30).unwrap(), Some("WIDGET!".into()));
+
+        // And it should survive a roundtrip
+        let mut buf = BytesMut::new();
+        selected.write(&mut buf).unwrap();
+        let (read, _) = ImprintRecord::read(buf.freeze()).unwrap();
+        assert_eq!(read.get_value(30).unwrap(), Some("WIDGET!".into()));
+
+        // And a reference to a deleted field should be left out like a missing one
+        let deleted = test_record(IntegerEncoding::Fixed, vec![(1, Value::Tombstone)]);
+        let selected = deleted.select(&[(10, Expr::Field(1))]).unwrap();
+        assert!(selected.directory.is_empty());
+
+        // And records selected the same way should share a schema id
+        let other = test_record(
+            IntegerEncoding::Varint,
+            vec![(1, 5i64.into()), (2, 0.5f64.into()), (3, "gadget".into())],
+        );
+        let schema_id = record.select(&outputs).unwrap().header.schema_id;
+        assert_eq!(other.select(&outputs).unwrap().header.schema_id, schema_id);
+        let fewer = record.select(&outputs[..2]).unwrap();
+        assert_ne!(fewer.header.schema_id, schema_id);
+
+        // And duplicate output IDs should be rejected
+        let duplicate = [(1, Expr::Field(1)), (1, Expr::Field(2))];
+        assert!(matches!(
+            record.select(&duplicate),
+            Err(ImprintError::SchemaError(_))
+        ));
+    }
+
+    #[test]
+    fn should_remap_field_ids_without_touching_payload() {
+        // Given a record with fields 1, 3, 5 and 7
+        let record = create_test_record();
+
+        // When moving fields 1 and 3 past the others
+        let mapping = HashMap::from([(1, 10), (3, 2)]);
+        let remapped = record.remap(&mapping).unwrap();
+
+        // Then the directory should be renumbered and re-sorted
+        let ids: Vec<u32> = remapped.directory.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![2, 5, 7, 10]);
+        assert_eq!(remapped.get_value(10).unwrap(), Some(42.into()));
+        assert_eq!(remapped.get_value(2).unwrap(), Some("hello".into()));
+        assert_eq!(remapped.get_value(1).unwrap(), None);
+
+        // And the payload should be shared as is
+        assert_eq!(
+            remapped.payload.get().unwrap(),
+            record.payload.get().unwrap()
+        );
+
+        // And it should survive a roundtrip
+        let mut buf = BytesMut::new();
+        remapped.write(&mut buf).unwrap();
+        let (read, _) = ImprintRecord::read(buf.freeze()).unwrap();
+        assert_eq!(read.get_value(10).unwrap(), Some(42.into()));
+        assert_eq!(read.get_value(7).unwrap(), Some(vec![1, 2, 3].into()));
+
+        // And the schema id should only change if fields were renumbered
+        assert_ne!(remapped.header.schema_id, record.header.schema_id);
+        let unchanged = record.remap(&HashMap::from([(1, 1), (9, 2)])).unwrap();
+        assert_eq!(unchanged.header.schema_id, record.header.schema_id);
+
+        // And colliding IDs should be rejected
+        let colliding = HashMap::from([(1, 5)]);
+        assert!(matches!(
+            record.remap(&colliding),
+            Err(ImprintError::SchemaError(_))
+        ));
+    }
+
+    #[test]
+    fn should_translate_fields_between_fieldspaces_by_name() {
+        // Given two fieldspaces naming the same fields differently
+        let record = create_test_record();
+        let mut registry = SchemaRegistry::new();
+        for (id, name) in [(1, "count"), (3, "greeting"), (5, "flag"), (7, "values")] {
+            registry.register_field_name(1, id, name);
+        }
+        for (id, name) in [(4, "count"), (3, "greeting"), (2, "flag"), (1, "values")] {
+            registry.register_field_name(2, id, name);
+        }
+
+        // When translating the record into the second fieldspace
+        let translated = record.translate(&registry, 2).unwrap();
+
+        // Then the fields should carry their IDs in that fieldspace
+        assert_eq!(translated.header.schema_id.fieldspace_id, 2);
+        assert_eq!(translated.get_value(4).unwrap(), Some(42.into()));
+        assert_eq!(translated.get_value(2).unwrap(), Some(true.into()));
+        assert_eq!(translated.get_value(1).unwrap(), Some(vec![1, 2, 3].into()));
+
+        // And fields without a counterpart should be rejected
+        registry.register_field_name(2, 1, "numbers");
+        assert!(matches!(
+            record.translate(&registry, 2),
+            Err(ImprintError::SchemaError(_))
+        ));
+    }
+
+    #[test]
+    fn should_update_insert_and_delete_fields_by_splicing() {
+        // Given a record with fields 1, 3, 5 and 7
+        let record = create_test_record();
+
+        // When updating, inserting and deleting fields
+        let updated = record.with_field(3, "goodbye, world".into()).unwrap();
+        let inserted = record.with_field(4, 1.5f64.into()).unwrap();
+        let deleted = record.without_field(5).unwrap();
+
+        // Then only the changed field should differ
+        assert_eq!(updated.get_value(3).unwrap(), Some("goodbye, world".into()));
+        assert_eq!(
+            updated.get_raw_bytes(7).unwrap(),
+            record.get_raw_bytes(7).unwrap()
+        );
+        assert_eq!(updated.header.schema_id, record.header.schema_id);
+        let ids: Vec<u32> = inserted.directory.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![1, 3, 4, 5, 7]);
+        assert_eq!(inserted.get_value(4).unwrap(), Some(1.5f64.into()));
+        let ids: Vec<u32> = deleted.directory.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![1, 3, 7]);
+        assert_eq!(deleted.get_value(7).unwrap(), Some(vec![1, 2, 3].into()));
+
+        // And records whose fields changed should get the schema id of their new fields
+        assert_ne!(inserted.header.schema_id, record.header.schema_id);
+        let reinserted = deleted.with_field(4, 1.5f64.into()).unwrap();
+        let without = inserted.without_field(5).unwrap();
+        assert_eq!(reinserted.header.schema_id, without.header.schema_id);
+
+        // And deleting a missing field should leave the record as it was
+        let unchanged = record.without_field(6).unwrap();
+        assert_eq!(
+            unchanged.payload.get().unwrap(),
+            record.payload.get().unwrap()
+        );
+        assert_eq!(unchanged.header, record.header);
+
+        // And each result should survive a roundtrip
+        for record in [updated, inserted, deleted] {
+            let mut buf = BytesMut::new();
+            record.write(&mut buf).unwrap();
+            let (read, _) = ImprintRecord::read(buf.freeze()).unwrap();
+            for entry in &record.directory {
+                assert_eq!(
+                    read.get_value(entry.id).unwrap(),
+                    record.get_value(entry.id).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn should_apply_batch_updates_in_order() {
+        // Given a record with fields 1, 3, 5 and 7
+        let record = create_test_record();
+
+        // When applying a batch with repeated and out of order updates
+        let updated = record
+            .with_updates(vec![
+                (9, Some("new".into())),
+                (1, Some(1.into())),
+                (5, None),
+                (1, Some(2.into())),
+                (0, Some(false.into())),
+                (3, None),
+                (3, Some("back".into())),
+            ])
+            .unwrap();
+
+        // Then the last update to each field should win
+        let ids: Vec<u32> = updated.directory.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![0, 1, 3, 7, 9]);
+        assert_eq!(updated.get_value(0).unwrap(), Some(false.into()));
+        assert_eq!(updated.get_value(1).unwrap(), Some(2.into()));
+        assert_eq!(updated.get_value(3).unwrap(), Some("back".into()));
+        assert_eq!(updated.get_value(7).unwrap(), Some(vec![1, 2, 3].into()));
+        assert_eq!(updated.get_value(9).unwrap(), Some("new".into()));
+        assert_eq!(updated.wasted_bytes(), 0);
+    }
+
+    #[test]
+    fn should_delete_fields_with_tombstones_when_merging() {
+        // Given an update deleting field 3 and a base record with fields 1, 3, 5 and 7
+        let base = create_test_record();
+        let update = test_record(
+            IntegerEncoding::Fixed,
+            vec![(1, 43.into()), (3, Value::Tombstone)],
+        );
+
+        // When merging the update over the base
+        let merged = update.merge(&base).unwrap();
+
+        // Then the deleted field should be gone and the others merged as usual
+        let ids: Vec<u32> = merged.directory.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![1, 5, 7]);
+        assert_eq!(merged.get_value(1).unwrap(), Some(43.into()));
+
+        // And a tombstone losing to a value should not delete it
+        let merged = base.merge(&update).unwrap();
+        assert_eq!(merged.get_value(3).unwrap(), Some("hello".into()));
+
+        // And kept tombstones should delete the field in a later merge
+        let options = MergeOptions {
+            keep_tombstones: true,
+            check_conflicts: true,
+            ..Default::default()
+        };
+        let later = test_record(
+            IntegerEncoding::Fixed,
+            vec![(3, Value::Tombstone), (9, true.into())],
+        );
+        let combined = later.merge_with_opts(&update, options).unwrap();
+        assert_eq!(combined.get_value(3).unwrap(), Some(Value::Tombstone));
+        let merged = combined.merge(&base).unwrap();
+        assert_eq!(merged.get_value(3).unwrap(), None);
+        assert_eq!(merged.get_value(9).unwrap(), Some(true.into()));
+
+        // And a tombstone should survive a roundtrip
+        let mut buf = BytesMut::new();
+        combined.write(&mut buf).unwrap();
+        let (read, _) = ImprintRecord::read(buf.freeze()).unwrap();
+        assert_eq!(read.directory, combined.directory);
+    }
+
+    #[test]
+    fn should_reject_nested_tombstones() {
+        // Given values holding a tombstone below the top level
+        let nested = [
+            Value::Array(vec![1.into(), Value::Tombstone]),
+            Value::Union(Box::new(Value::Tombstone)),
+        ];
+
+        // Then they should not be written
+        for value in nested {
+            assert!(matches!(
+                value.write(&mut BytesMut::new()),
+                Err(ImprintError::SchemaError(_))
+            ));
+        }
+
+        // And crafted bytes holding one should not be read
+        let tombstone = TypeCode::Tombstone as u8;
+        let crafted = [
+            (TypeCode::Array, vec![1, tombstone]),
+            (TypeCode::Array, vec![1, tombstone | 0x80, 0x01]),
+            (
+                TypeCode::Map,
+                vec![1, TypeCode::String as u8, tombstone, 1, b'a'],
+            ),
+            (TypeCode::Union, vec![tombstone]),
+        ];
+        for (type_code, bytes) in crafted {
+            assert!(matches!(
+                Value::read(type_code, Bytes::from(bytes)),
+                Err(ImprintError::SchemaError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn should_deep_merge_nested_rows_maps_and_arrays() {
+        // Given two sources enriching the same customer subrecord, preferences and tags
+        let customer = |fields| Value::Row(Box::new(test_record(IntegerEncoding::Fixed, fields)));
+        let map = |entries: Vec<(&str, Value)>| {
+            Value::Map(entries.into_iter().map(|(k, v)| (k.into(), v)).collect())
+        };
+        let first = test_record(
+            IntegerEncoding::Fixed,
+            vec![
+                (1, customer(vec![(1, "Ada".into()), (2, "London".into())])),
+                (
+                    2,
+                    map(vec![
+                        ("email", map(vec![("opt_in", 1.into())])),
+                        ("sms", map(vec![("opt_in", 0.into())])),
+                    ]),
+                ),
+                (3, vec![1, 2].into()),
+            ],
+        );
+        let second = test_record(
+            IntegerEncoding::Fixed,
+            vec![
+                (1, customer(vec![(2, "Paris".into()), (3, 36.into())])),
+                (
+                    2,
+                    map(vec![
+                        (
+                            "email",
+                            map(vec![("opt_in", 0.into()), ("weekly", 1.into())]),
+                        ),
+                        ("push", map(vec![("opt_in", 1.into())])),
+                    ]),
+                ),
+                (3, vec![3].into()),
+                (4, true.into()),
+            ],
+        );
+
+        // When merging them deeply
+        let options = MergeOptions {
+            deep: true,
+            check_conflicts: true,
+            ..Default::default()
+        };
+        let merged = first.merge_with_opts(&second, options).unwrap();
+
+        // Then nested rows and maps should combine with the first winning conflicts
+        let Some(Value::Row(row)) = merged.get_value(1).unwrap() else {
+            panic!("expected a row");
+        };
+        assert_eq!(row.get_value(1).unwrap(), Some("Ada".into()));
+        assert_eq!(row.get_value(2).unwrap(), Some("London".into()));
+        assert_eq!(row.get_value(3).unwrap(), Some(36.into()));
+        assert_eq!(
+            merged.get_value(2).unwrap(),
+            Some(map(vec![
+                (
+                    "email",
+                    map(vec![("opt_in", 1.into()), ("weekly", 1.into())])
+                ),
+                ("sms", map(vec![("opt_in", 0.into())])),
+                ("push", map(vec![("opt_in", 1.into())])),
+            ]))
+        );
+
+        // And arrays should be replaced by default, or concatenated if asked
+        assert_eq!(merged.get_value(3).unwrap(), Some(vec![1, 2].into()));
+        let options = MergeOptions {
+            arrays: ArrayMerge::Concat,
+            ..options
+        };
+        let concatenated = first.merge_with_opts(&second, options).unwrap();
+        assert_eq!(
+            concatenated.get_value(3).unwrap(),
+            Some(vec![1, 2, 3].into())
+        );
+
+        // And the result should survive a roundtrip
+        let mut buf = BytesMut::new();
+        concatenated.write(&mut buf).unwrap();
+        let (read, _) = ImprintRecord::read(buf.freeze()).unwrap();
+        assert_eq!(read.get_value(2).unwrap(), merged.get_value(2).unwrap());
+        assert_eq!(read.get_value(4).unwrap(), Some(true.into()));
+
+        // And a shallow merge should still keep the first row whole
+        let shallow = first.merge(&second).unwrap();
+        assert_eq!(
+            shallow.get_raw_bytes(1).unwrap(),
+            first.get_raw_bytes(1).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_check_conflicts_within_deep_merged_maps() {
+        // Given maps holding different types under the same key
+        let map = |value: Value| Value::Map([("limit".into(), value)].into());
+        let first = test_record(IntegerEncoding::Fixed, vec![(1, map(10.into()))]);
+        let second = test_record(IntegerEncoding::Fixed, vec![(1, map("none".into()))]);
+
+        // Then a deep merge checking conflicts should reject them
+        let options = MergeOptions {
+            deep: true,
+            check_conflicts: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            first.merge_with_opts(&second, options),
+            Err(ImprintError::MergeConflict { field_id: 1, .. })
+        ));
+
+        // And without checks the first value should win
+        let options = MergeOptions {
+            check_conflicts: false,
+            ..options
+        };
+        let merged = first.merge_with_opts(&second, options).unwrap();
+        assert_eq!(merged.get_value(1).unwrap(), Some(map(10.into())));
+    }
+
+    #[test]
+    fn should_check_conflicts_between_disjoint_deep_merged_map_keys() {
+        // Given maps with disjoint keys holding values of different types
+        let first = Value::Map([("a".into(), 10.into())].into());
+        let second = Value::Map([("b".into(), "none".into())].into());
+        let first = test_record(IntegerEncoding::Fixed, vec![(1, first.clone())]);
+        let second = test_record(IntegerEncoding::Fixed, vec![(1, second)]);
+
+        // Then a deep merge checking conflicts should reject them
+        let options = MergeOptions {
+            deep: true,
+            check_conflicts: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            first.merge_with_opts(&second, options),
+            Err(ImprintError::MergeConflict {
+                field_id: 1,
+                first: TypeCode::Int32,
+                second: TypeCode::String,
+            })
+        ));
+
+        // And without checks the first map's entries should be kept
+        let options = MergeOptions {
+            check_conflicts: false,
+            ..options
+        };
+        let merged = first.merge_with_opts(&second, options).unwrap();
+        assert_eq!(merged.get_value(1).unwrap(), first.get_value(1).unwrap());
+    }
 }
@@ -0,0 +1,371 @@
+use std::cmp::Ordering;
+
+use bytes::Bytes;
+
+use crate::{
+    error::ImprintError,
+    serde::ValueRead,
+    types::{ImprintRecord, TypeCode, Value},
+    varint,
+};
+
+impl Value {
+    /// A total order over all values, so that they can be sorted, used as
+    /// `BTreeMap` keys (through [`OrderedValue`]) and aggregated with min and max.
+    ///
+    /// Values of different types order by their type code, so `Null` sorts before
+    /// everything else. Values of the same type order as follows:
+    ///
+    /// - numbers, dates, times, enums and UUIDs by value or ordinal
+    /// - floats by [`f64::total_cmp`], so `-0.0` sorts before `0.0` and NaNs
+    ///   sort at either end by their sign
+    /// - bytes and strings lexicographically by their bytes
+    /// - timestamps by instant, then by unit and UTC flag
+    /// - decimals numerically, so `1.0` and `1.00` are equal
+    /// - arrays lexicographically by their elements, maps by their entries in
+    ///   key order, and rows by their fieldspace and then their fields in ID order
+    /// - unions by their branch and then their value
+    pub fn total_cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Int8(a), Value::Int8(b)) => a.cmp(b),
+            (Value::Int16(a), Value::Int16(b)) => a.cmp(b),
+            (Value::Int32(a), Value::Int32(b)) | (Value::Date(a), Value::Date(b)) => a.cmp(b),
+            (Value::Int64(a), Value::Int64(b)) | (Value::Time(a), Value::Time(b)) => a.cmp(b),
+            (Value::UInt8(a), Value::UInt8(b)) => a.cmp(b),
+            (Value::UInt16(a), Value::UInt16(b)) => a.cmp(b),
+            (Value::UInt32(a), Value::UInt32(b)) | (Value::Enum(a), Value::Enum(b)) => a.cmp(b),
+            (Value::UInt64(a), Value::UInt64(b)) => a.cmp(b),
+            (Value::Float32(a), Value::Float32(b)) => a.total_cmp(b),
+            (Value::Float64(a), Value::Float64(b)) => a.total_cmp(b),
+            (Value::Bytes(a), Value::Bytes(b)) | (Value::FixedBytes(a), Value::FixedBytes(b)) => {
+                a.cmp(b)
+            }
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Uuid(a), Value::Uuid(b)) => a.cmp(b),
+            (Value::Timestamp(a), Value::Timestamp(b)) => {
+                (a.as_nanos(), a.metadata()).cmp(&(b.as_nanos(), b.metadata()))
+            }
+            (Value::Decimal(a), Value::Decimal(b)) => a.cmp(b),
+            (Value::Array(a), Value::Array(b)) => cmp_sequences(
+                a.iter().zip(b).map(|(a, b)| a.total_cmp(b)),
+                a.len(),
+                b.len(),
+            ),
+            (Value::Map(a), Value::Map(b)) => {
+                let mut a: Vec<_> = a.iter().collect();
+                let mut b: Vec<_> = b.iter().collect();
+                a.sort_unstable_by(|x, y| x.0.cmp(y.0));
+                b.sort_unstable_by(|x, y| x.0.cmp(y.0));
+                let entries = a
+                    .iter()
+                    .zip(&b)
+                    .map(|(a, b)| a.0.cmp(b.0).then_with(|| a.1.total_cmp(b.1)));
+                cmp_sequences(entries, a.len(), b.len())
+            }
+            (Value::Row(a), Value::Row(b)) => cmp_rows(a, b),
+            (Value::Union(a), Value::Union(b)) => a.total_cmp(b),
+            (a, b) => (a.type_code() as u8).cmp(&(b.type_code() as u8)),
+        }
+    }
+}
+
+/// Compare element by element, with a prefix sorting before the sequences it starts
+fn cmp_sequences(
+    mut elements: impl Iterator<Item = Ordering>,
+    len: usize,
+    other_len: usize,
+) -> Ordering {
+    elements
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| len.cmp(&other_len))
+}
+
+fn cmp_rows(a: &ImprintRecord, b: &ImprintRecord) -> Ordering {
+    // missing and deleted fields compare as nulls, and so do fields that fail to
+    // decode since an order can't fail
+    let value = |record: &ImprintRecord, id| match record.get_value(id) {
+        Ok(Some(Value::Tombstone)) | Ok(None) | Err(_) => Value::Null,
+        Ok(Some(value)) => value,
+    };
+    let fields = a.directory.iter().zip(&b.directory).map(|(x, y)| {
+        x.id.cmp(&y.id)
+            .then_with(|| value(a, x.id).total_cmp(&value(b, y.id)))
+    });
+    let fieldspaces = a
+        .header
+        .schema_id
+        .fieldspace_id
+        .cmp(&b.header.schema_id.fieldspace_id);
+    fieldspaces.then_with(|| cmp_sequences(fields, a.directory.len(), b.directory.len()))
+}
+
+/// A [`Value`] ordered by [`Value::total_cmp`], for sorting, `BTreeMap` keys and
+/// min/max aggregations. Unlike with `Value`, a NaN equals itself and `-0.0`
+/// differs from `0.0`.
+#[derive(Debug, Clone)]
+pub struct OrderedValue(pub Value);
+
+impl PartialEq for OrderedValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for OrderedValue {}
+
+impl PartialOrd for OrderedValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderedValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl From<Value> for OrderedValue {
+    fn from(value: Value) -> Self {
+        OrderedValue(value)
+    }
+}
+
+impl From<OrderedValue> for Value {
+    fn from(value: OrderedValue) -> Self {
+        value.0
+    }
+}
+
+impl ImprintRecord {
+    /// Compare a field of this record with the same field of another by
+    /// [`Value::total_cmp`], without decoding it where the type allows. Missing
+    /// and deleted fields compare as nulls.
+    pub fn cmp_field(
+        &self,
+        other: &ImprintRecord,
+        field_id: u32,
+    ) -> Result<Ordering, ImprintError> {
+        let raw = |record: &ImprintRecord| -> Result<(TypeCode, Bytes), ImprintError> {
+            match record.entry(field_id) {
+                Some(entry) if entry.type_code != TypeCode::Tombstone => {
+                    Ok((entry.type_code, record.entry_bytes(entry)?))
+                }
+                _ => Ok((TypeCode::Null, Bytes::new())),
+            }
+        };
+        let (type_code, bytes) = raw(self)?;
+        let (other_type, other_bytes) = raw(other)?;
+        cmp_raw(type_code, &bytes, other_type, &other_bytes)
+    }
+}
+
+/// Compare two encoded field values by [`Value::total_cmp`].
+///
+/// Fields of different types compare by type code, and integers, floats, bytes,
+/// strings and UUIDs compare straight from their encoding. Everything else is
+/// decoded first.
+pub fn cmp_raw(
+    type_code: TypeCode,
+    bytes: &Bytes,
+    other_type: TypeCode,
+    other_bytes: &Bytes,
+) -> Result<Ordering, ImprintError> {
+    if type_code.logical() != other_type.logical() {
+        return Ok((type_code.logical() as u8).cmp(&(other_type.logical() as u8)));
+    }
+    if type_code != other_type {
+        // a varint and a fixed-width encoding of the same integer type
+        return cmp_decoded(type_code, bytes, other_type, other_bytes);
+    }
+
+    let (a, b) = (bytes.as_ref(), other_bytes.as_ref());
+    let ordering = match type_code {
+        TypeCode::Null => Ordering::Equal,
+        TypeCode::Bool | TypeCode::UInt8 => fixed::<1>(a)?.cmp(&fixed::<1>(b)?),
+        TypeCode::Int8 => i8::from_le_bytes(fixed(a)?).cmp(&i8::from_le_bytes(fixed(b)?)),
+        TypeCode::Int16 => i16::from_le_bytes(fixed(a)?).cmp(&i16::from_le_bytes(fixed(b)?)),
+        TypeCode::Int32 | TypeCode::Date => {
+            i32::from_le_bytes(fixed(a)?).cmp(&i32::from_le_bytes(fixed(b)?))
+        }
+        TypeCode::Int64 | TypeCode::Time => {
+            i64::from_le_bytes(fixed(a)?).cmp(&i64::from_le_bytes(fixed(b)?))
+        }
+        TypeCode::UInt16 => u16::from_le_bytes(fixed(a)?).cmp(&u16::from_le_bytes(fixed(b)?)),
+        TypeCode::UInt32 => u32::from_le_bytes(fixed(a)?).cmp(&u32::from_le_bytes(fixed(b)?)),
+        TypeCode::UInt64 => u64::from_le_bytes(fixed(a)?).cmp(&u64::from_le_bytes(fixed(b)?)),
+        TypeCode::Float32 => {
+            f32::from_le_bytes(fixed(a)?).total_cmp(&f32::from_le_bytes(fixed(b)?))
+        }
+        TypeCode::Float64 => {
+            f64::from_le_bytes(fixed(a)?).total_cmp(&f64::from_le_bytes(fixed(b)?))
+        }
+        TypeCode::Bytes | TypeCode::String => length_prefixed(a)?.cmp(length_prefixed(b)?),
+        // top-level fixed-size bytes span exactly the field
+        TypeCode::FixedBytes => a.cmp(b),
+        TypeCode::Uuid => fixed::<16>(a)?.cmp(&fixed::<16>(b)?),
+        _ => return cmp_decoded(type_code, bytes, other_type, other_bytes),
+    };
+    Ok(ordering)
+}
+
+fn cmp_decoded(
+    type_code: TypeCode,
+    bytes: &Bytes,
+    other_type: TypeCode,
+    other_bytes: &Bytes,
+) -> Result<Ordering, ImprintError> {
+    let (value, _) = Value::read(type_code, bytes.clone())?;
+    let (other, _) = Value::read(other_type, other_bytes.clone())?;
+    Ok(value.total_cmp(&other))
+}
+
+fn fixed<const N: usize>(bytes: &[u8]) -> Result<[u8; N], ImprintError> {
+    bytes
+        .get(..N)
+        .and_then(|head| head.try_into().ok())
+        .ok_or(ImprintError::BufferUnderflow {
+            needed: N,
+            available: bytes.len(),
+        })
+}
+
+/// The contents of a value written as a varint length followed by its bytes
+pub(crate) fn length_prefixed(bytes: &[u8]) -> Result<&[u8], ImprintError> {
+    let (len, prefix) = varint::decode_slice(bytes)?;
+    let end = prefix + len as usize;
+    bytes.get(prefix..end).ok_or(ImprintError::BufferUnderflow {
+        needed: end,
+        available: bytes.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Decimal, IntegerEncoding, types::MapKey, writer::test_record};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn should_order_values_totally() {
+        // Given values of several types, including floats without a partial order
+        let mut values: Vec<OrderedValue> = vec![
+            Value::Float64(f64::NAN),
+            "b".into(),
+            Value::Float64(0.0),
+            Value::Null,
+            Value::Float64(-0.0),
+            2.into(),
+            Value::Array(vec![1.into(), 2.into()]),
+            "a".into(),
+            (-1).into(),
+            Value::Float64(f64::NEG_INFINITY),
+            Value::Array(vec![1.into()]),
+            true.into(),
+        ]
+        .into_iter()
+        .map(OrderedValue::from)
+        .collect();
+
+        // When sorting them
+        values.sort();
+
+        // Then they should be ordered by type code and then by value
+        let sorted: Vec<Value> = values.into_iter().map(Value::from).collect();
+        let expected: Vec<Value> = vec![
+            Value::Null,
+            true.into(),
+            (-1).into(),
+            2.into(),
+            Value::Float64(f64::NEG_INFINITY),
+            Value::Float64(-0.0),
+            Value::Float64(0.0),
+            Value::Float64(f64::NAN),
+            "a".into(),
+            "b".into(),
+            Value::Array(vec![1.into()]),
+            Value::Array(vec![1.into(), 2.into()]),
+        ];
+        assert_eq!(format!("{:?}", sorted), format!("{:?}", expected));
+    }
+
+    #[test]
+    // nested rows cache their decompressed payload, which never changes their order
+    #[allow(clippy::mutable_key_type)]
+    fn should_key_btree_maps_by_value() {
+        // Given equal decimals at different scales and maps in different orders
+        let map = |order: &[i32]| {
+            let m: HashMap<MapKey, Value> = order.iter().map(|&k| (k.into(), k.into())).collect();
+            Value::Map(m)
+        };
+        let mut counts = BTreeMap::new();
+        for value in [
+            Decimal::new(10, 3, 1).unwrap().into(),
+            Decimal::new(100, 4, 2).unwrap().into(),
+            map(&[1, 2, 3]),
+            map(&[3, 2, 1]),
+            Value::Float64(f64::NAN),
+            Value::Float64(f64::NAN),
+        ] {
+            // When counting them in a BTreeMap
+            *counts.entry(OrderedValue(value)).or_insert(0) += 1;
+        }
+
+        // Then equal values should share a key
+        assert_eq!(counts.values().collect::<Vec<_>>(), vec![&2, &2, &2]);
+
+        // And min and max should follow the same order
+        let ints = [3, -7, 12].map(|v| OrderedValue(v.into()));
+        assert_eq!(ints.iter().min().unwrap().0, Value::Int32(-7));
+        assert_eq!(ints.iter().max().unwrap().0, Value::Int32(12));
+    }
+
+    #[test]
+    fn should_compare_raw_fields_like_values() {
+        // Given pairs of rows with fields of several types, one written with varints
+        let fields = |i: i64, s: &str| {
+            vec![
+                (1, Value::Int64(i)),
+                (2, s.into()),
+                (3, Value::Float32(i as f32)),
+                (4, Value::Bytes(s.as_bytes().to_vec())),
+                (5, Decimal::new(i as i128, 10, 2).unwrap().into()),
+                (6, Value::Array(vec![s.into()])),
+            ]
+        };
+        let cases = [(-5, "ab", 3, "b"), (3, "b", 3, "b"), (300, "b", -2, "ab")];
+        for (i, s, other_i, other_s) in cases {
+            let a = test_record(IntegerEncoding::Fixed, fields(i, s));
+            let b = test_record(IntegerEncoding::Varint, fields(other_i, other_s));
+
+            // When comparing each field raw
+            for id in 1..=6 {
+                let actual = a.cmp_field(&b, id).unwrap();
+
+                // Then it should match comparing the decoded values
+                let expected = a
+                    .get_value(id)
+                    .unwrap()
+                    .unwrap()
+                    .total_cmp(&b.get_value(id).unwrap().unwrap());
+                assert_eq!(actual, expected, "field {} of {:?}", id, (i, s));
+            }
+        }
+
+        // And a missing field should compare as null
+        let empty = test_record(IntegerEncoding::Fixed, vec![]);
+        let a = test_record(IntegerEncoding::Fixed, fields(1, "a"));
+        assert_eq!(empty.cmp_field(&a, 1).unwrap(), Ordering::Less);
+        assert_eq!(empty.cmp_field(&empty, 1).unwrap(), Ordering::Equal);
+
+        // And so should a deleted one, on its own or in a nested row
+        let deleted = test_record(IntegerEncoding::Fixed, vec![(1, Value::Tombstone)]);
+        assert_eq!(deleted.cmp_field(&empty, 1).unwrap(), Ordering::Equal);
+        assert_eq!(deleted.cmp_field(&a, 1).unwrap(), Ordering::Less);
+        let row = |record: ImprintRecord| Value::Row(Box::new(record));
+        let null = test_record(IntegerEncoding::Fixed, vec![(1, Value::Null)]);
+        assert_eq!(row(deleted).total_cmp(&row(null)), Ordering::Equal);
+    }
+}
@@ -0,0 +1,231 @@
+use bytes::BytesMut;
+use std::cmp::Ordering;
+
+use crate::{
+    error::ImprintError,
+    ops::splice_fields,
+    types::{DirectoryEntry, Flags, Header, ImprintRecord, Payload, TypeCode},
+    varint,
+};
+
+/// The field IDs that differ between two records, each in ascending order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordDiff {
+    /// Fields only in the new record
+    pub added: Vec<u32>,
+    /// Fields only in the old record
+    pub removed: Vec<u32>,
+    /// Fields in both records with a different type code or different bytes
+    pub changed: Vec<u32>,
+}
+
+impl RecordDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Build a patch record from this diff and the new record it was computed
+    /// against. The patch has the header of the new record, holds the bytes of the
+    /// added and changed fields as they are and a tombstone for each removed field.
+    /// Added or changed fields can't be tombstones themselves, since applying the
+    /// patch would remove them.
+    pub fn to_patch(&self, new: &ImprintRecord) -> Result<ImprintRecord, ImprintError> {
+        let mut ids: Vec<u32> = self
+            .added
+            .iter()
+            .chain(&self.changed)
+            .chain(&self.removed)
+            .copied()
+            .collect();
+        ids.sort_unstable();
+
+        let mut directory = Vec::with_capacity(ids.len());
+        let mut payload = BytesMut::new();
+        for id in ids {
+            let offset = varint::checked_u32(payload.len(), "field offset")?;
+            match new.entry(id) {
+                Some(entry) if entry.type_code == TypeCode::Tombstone => {
+                    return Err(ImprintError::SchemaError(format!(
+                        "field {} of the new record is a tombstone, which a patch can't hold",
+                        id
+                    )));
+                }
+                Some(entry) => {
+                    payload.extend_from_slice(&new.entry_bytes(entry)?);
+                    directory.push(DirectoryEntry { offset, ..*entry });
+                }
+                None => directory.push(DirectoryEntry {
+                    id,
+                    type_code: TypeCode::Tombstone,
+                    offset,
+                    length: 0,
+                }),
+            }
+        }
+
+        Ok(ImprintRecord {
+            header: Header {
+                flags: new.header.flags.with(Flags::FIELD_DIRECTORY, true),
+                schema_id: new.header.schema_id,
+                payload_size: varint::checked_u32(payload.len(), "payload size")?,
+            },
+            directory,
+            payload: Payload::new(payload.freeze(), new.payload.compression()),
+        })
+    }
+}
+
+/// Compare the fields of two records by type code and raw bytes, without decoding
+/// them. A field re-encoded differently, such as a varint in place of a fixed
+/// width integer, counts as changed.
+pub fn diff(old: &ImprintRecord, new: &ImprintRecord) -> Result<RecordDiff, ImprintError> {
+    let mut result = RecordDiff::default();
+    let mut old_entries = old.directory.iter().peekable();
+    let mut new_entries = new.directory.iter().peekable();
+
+    loop {
+        match (old_entries.peek(), new_entries.peek()) {
+            (None, None) => break,
+            (Some(_), None) => result.removed.extend(old_entries.by_ref().map(|e| e.id)),
+            (None, Some(_)) => result.added.extend(new_entries.by_ref().map(|e| e.id)),
+            (Some(o), Some(n)) => match o.id.cmp(&n.id) {
+                Ordering::Less => result.removed.push(old_entries.next().unwrap().id),
+                Ordering::Greater => result.added.push(new_entries.next().unwrap().id),
+                Ordering::Equal => {
+                    let (o, n) = (old_entries.next().unwrap(), new_entries.next().unwrap());
+                    if o.type_code != n.type_code || old.entry_bytes(o)? != new.entry_bytes(n)? {
+                        result.changed.push(n.id);
+                    }
+                }
+            },
+        }
+    }
+    Ok(result)
+}
+
+/// Apply a patch built by [`RecordDiff::to_patch`] to the record it was diffed
+/// from, removing the fields the patch has tombstones for. Fields are spliced in
+/// as raw bytes, so the result is byte-for-byte the new record the patch was built
+/// from, as laid out by [`compact`](crate::Compact::compact) and with a field
+/// directory.
+pub fn apply_patch(
+    base: &ImprintRecord,
+    patch: &ImprintRecord,
+) -> Result<ImprintRecord, ImprintError> {
+    let updates = patch
+        .directory
+        .iter()
+        .map(|entry| match entry.type_code {
+            TypeCode::Tombstone => Ok((entry.id, None)),
+            type_code => Ok((entry.id, Some((type_code, patch.entry_bytes(entry)?)))),
+        })
+        .collect::<Result<Vec<_>, ImprintError>>()?;
+
+    let (directory, payload) = splice_fields(base, updates, |(type_code, bytes), buf| {
+        buf.extend_from_slice(&bytes);
+        Ok(type_code)
+    })?;
+
+    Ok(ImprintRecord {
+        header: Header {
+            flags: patch.header.flags,
+            schema_id: patch.header.schema_id,
+            payload_size: varint::checked_u32(payload.len(), "payload size")?,
+        },
+        directory,
+        payload: Payload::new(payload.freeze(), patch.payload.compression()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Compact, IntegerEncoding, Merge, Read, Value, serde::Write, writer::test_record};
+    use bytes::Bytes;
+
+    fn to_bytes(record: &ImprintRecord) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        record.write(&mut buf).unwrap();
+        buf.to_vec()
+    }
+
+    #[test]
+    fn should_diff_added_removed_and_changed_fields() {
+        // Given an old and a new version of a row
+        let old = test_record(
+            IntegerEncoding::Fixed,
+            vec![
+                (1, 7i64.into()),
+                (2, "pending".into()),
+                (3, 1.5f64.into()),
+                (5, vec![1, 2].into()),
+            ],
+        );
+        let new = test_record(
+            IntegerEncoding::Varint,
+            vec![
+                (1, 7i64.into()),
+                (2, "shipped".into()),
+                (4, true.into()),
+                (5, vec![1, 2].into()),
+            ],
+        );
+
+        // When diffing them
+        let diff = diff(&old, &new).unwrap();
+
+        // Then re-encoded, rewritten, added and removed fields should be reported
+        assert_eq!(
+            diff,
+            RecordDiff {
+                added: vec![4],
+                removed: vec![3],
+                changed: vec![1, 2],
+            }
+        );
+        assert!(super::diff(&new, &new).unwrap().is_empty());
+    }
+
+    #[test]
+    fn should_reproduce_new_record_from_patch() {
+        // Given an old row and a new one with a field changed, added and removed
+        let old = test_record(
+            IntegerEncoding::Fixed,
+            vec![(1, 42.into()), (2, "pending".into()), (3, 1.5f64.into())],
+        );
+        let new = test_record(
+            IntegerEncoding::Fixed,
+            vec![(1, 42.into()), (2, "shipped".into()), (4, "note".into())],
+        );
+
+        // When shipping a patch through its serialized form
+        let patch = diff(&old, &new).unwrap().to_patch(&new).unwrap();
+        let (patch, _) = ImprintRecord::read(Bytes::from(to_bytes(&patch))).unwrap();
+        assert_eq!(patch.get_value(1).unwrap(), None);
+        assert_eq!(patch.get_value(3).unwrap(), Some(Value::Tombstone));
+
+        // Then applying it should rebuild the new record byte-for-byte
+        let applied = apply_patch(&old, &patch).unwrap();
+        assert_eq!(to_bytes(&applied), to_bytes(&new));
+
+        // And records laid out by a merge should match once compacted
+        let merged = old.merge(&new).unwrap();
+        let patch = diff(&old, &merged).unwrap().to_patch(&merged).unwrap();
+        let applied = apply_patch(&old, &patch).unwrap();
+        assert_eq!(to_bytes(&applied), to_bytes(&merged.compact().unwrap()));
+    }
+
+    #[test]
+    fn should_reject_patches_to_records_holding_tombstones() {
+        // Given a new row that keeps a tombstone for a field it deletes
+        let old = test_record(IntegerEncoding::Fixed, vec![(1, 42.into())]);
+        let new = test_record(IntegerEncoding::Fixed, vec![(1, Value::Tombstone)]);
+
+        // Then building a patch to it should fail, since applying it would drop the tombstone
+        let diff = diff(&old, &new).unwrap();
+        assert!(matches!(
+            diff.to_patch(&new),
+            Err(ImprintError::SchemaError(_))
+        ));
+    }
+}
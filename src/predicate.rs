@@ -0,0 +1,418 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ops::Not;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    error::ImprintError,
+    order::{cmp_raw, length_prefixed},
+    serde::{Write, for_each_element},
+    types::{ImprintRecord, TypeCode, Value},
+};
+
+/// A filter over the fields of a record. Predicates are compiled once with
+/// [`compile`](Self::compile) and then evaluated on the encoded bytes of each
+/// record, without decoding the fields they look at where the type allows.
+///
+/// Values compare by [`Value::total_cmp`]. Comparisons, `In`, `StartsWith` and
+/// `Contains` are false for null and missing fields, and for fields of another
+/// type than the literal, so `Ne` only matches non-null fields of the same type.
+/// `Not` inverts whatever its predicate returns.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Eq(u32, Value),
+    Ne(u32, Value),
+    Lt(u32, Value),
+    Le(u32, Value),
+    Gt(u32, Value),
+    Ge(u32, Value),
+    In(u32, Vec<Value>),
+    /// The field is null or missing
+    IsNull(u32),
+    /// The field is a string starting with the given prefix
+    StartsWith(u32, String),
+    /// The field is an array with an element equal to the value
+    Contains(u32, Value),
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    /// Both this predicate and `other`
+    pub fn and(self, other: Predicate) -> Predicate {
+        match self {
+            Predicate::And(mut predicates) => {
+                predicates.push(other);
+                Predicate::And(predicates)
+            }
+            predicate => Predicate::And(vec![predicate, other]),
+        }
+    }
+
+    /// Either this predicate or `other`
+    pub fn or(self, other: Predicate) -> Predicate {
+        match self {
+            Predicate::Or(mut predicates) => {
+                predicates.push(other);
+                Predicate::Or(predicates)
+            }
+            predicate => Predicate::Or(vec![predicate, other]),
+        }
+    }
+
+    /// Encode the literals of this predicate so it can be evaluated against the
+    /// encoded fields of many records
+    pub fn compile(&self) -> Result<CompiledPredicate, ImprintError> {
+        Ok(CompiledPredicate {
+            root: Node::compile(self)?,
+        })
+    }
+}
+
+impl Not for Predicate {
+    type Output = Predicate;
+
+    fn not(self) -> Predicate {
+        match self {
+            Predicate::Not(predicate) => *predicate,
+            predicate => Predicate::Not(Box::new(predicate)),
+        }
+    }
+}
+
+/// A [`Predicate`] with its literals encoded, ready to run against records
+#[derive(Debug, Clone)]
+pub struct CompiledPredicate {
+    root: Node,
+}
+
+impl CompiledPredicate {
+    /// Whether the record matches the predicate
+    pub fn matches(&self, record: &ImprintRecord) -> Result<bool, ImprintError> {
+        self.root.matches(record)
+    }
+}
+
+/// A value encoded the way it would be as a field
+#[derive(Debug, Clone)]
+struct Literal {
+    type_code: TypeCode,
+    bytes: Bytes,
+}
+
+impl Literal {
+    fn encode(value: &Value) -> Result<Self, ImprintError> {
+        let mut buf = BytesMut::new();
+        value.write(&mut buf)?;
+        Ok(Literal {
+            type_code: value.type_code(),
+            bytes: buf.freeze(),
+        })
+    }
+
+    /// How the encoded value compares to this literal, or `None` if it has another type
+    fn cmp_field(
+        &self,
+        type_code: TypeCode,
+        bytes: &Bytes,
+    ) -> Result<Option<Ordering>, ImprintError> {
+        if type_code.logical() != self.type_code.logical() {
+            return Ok(None);
+        }
+        cmp_raw(type_code, bytes, self.type_code, &self.bytes).map(Some)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Eq => ordering.is_eq(),
+            Comparison::Ne => ordering.is_ne(),
+            Comparison::Lt => ordering.is_lt(),
+            Comparison::Le => ordering.is_le(),
+            Comparison::Gt => ordering.is_gt(),
+            Comparison::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Compare(u32, Comparison, Literal),
+    In {
+        field_id: u32,
+        literals: Vec<Literal>,
+        /// Type code and key of the literals that can be looked up by their bytes
+        index: HashSet<(u8, Bytes)>,
+    },
+    IsNull(u32),
+    StartsWith(u32, Bytes),
+    Contains(u32, Literal),
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Not(Box<Node>),
+}
+
+impl Node {
+    fn compile(predicate: &Predicate) -> Result<Node, ImprintError> {
+        let compare = |field_id: &u32, comparison, value| {
+            Ok(Node::Compare(
+                *field_id,
+                comparison,
+                Literal::encode(value)?,
+            ))
+        };
+        match predicate {
+            Predicate::Eq(field_id, value) => compare(field_id, Comparison::Eq, value),
+            Predicate::Ne(field_id, value) => compare(field_id, Comparison::Ne, value),
+            Predicate::Lt(field_id, value) => compare(field_id, Comparison::Lt, value),
+            Predicate::Le(field_id, value) => compare(field_id, Comparison::Le, value),
+            Predicate::Gt(field_id, value) => compare(field_id, Comparison::Gt, value),
+            Predicate::Ge(field_id, value) => compare(field_id, Comparison::Ge, value),
+            Predicate::In(field_id, values) => {
+                let literals = values
+                    .iter()
+                    .map(Literal::encode)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut index = HashSet::new();
+                for literal in &literals {
+                    if let Some(key) = index_key(literal.type_code, &literal.bytes)? {
+                        index.insert((literal.type_code as u8, key));
+                    }
+                }
+                Ok(Node::In {
+                    field_id: *field_id,
+                    literals,
+                    index,
+                })
+            }
+            Predicate::IsNull(field_id) => Ok(Node::IsNull(*field_id)),
+            Predicate::StartsWith(field_id, prefix) => Ok(Node::StartsWith(
+                *field_id,
+                Bytes::copy_from_slice(prefix.as_bytes()),
+            )),
+            Predicate::Contains(field_id, value) => {
+                Ok(Node::Contains(*field_id, Literal::encode(value)?))
+            }
+            Predicate::And(predicates) => Ok(Node::And(
+                predicates
+                    .iter()
+                    .map(Node::compile)
+                    .collect::<Result<_, _>>()?,
+            )),
+            Predicate::Or(predicates) => Ok(Node::Or(
+                predicates
+                    .iter()
+                    .map(Node::compile)
+                    .collect::<Result<_, _>>()?,
+            )),
+            Predicate::Not(predicate) => Ok(Node::Not(Box::new(Node::compile(predicate)?))),
+        }
+    }
+
+    fn matches(&self, record: &ImprintRecord) -> Result<bool, ImprintError> {
+        match self {
+            Node::Compare(field_id, comparison, literal) => {
+                let Some((type_code, bytes)) = field(record, *field_id)? else {
+                    return Ok(false);
+                };
+                Ok(literal
+                    .cmp_field(type_code, &bytes)?
+                    .is_some_and(|ordering| comparison.holds(ordering)))
+            }
+            Node::In {
+                field_id,
+                literals,
+                index,
+            } => {
+                let Some((type_code, bytes)) = field(record, *field_id)? else {
+                    return Ok(false);
+                };
+                // only literals of the same type code can equal an indexed field
+                if let Some(key) = index_key(type_code, &bytes)? {
+                    return Ok(index.contains(&(type_code as u8, key)));
+                }
+                for literal in literals {
+                    if literal.cmp_field(type_code, &bytes)? == Some(Ordering::Equal) {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Node::IsNull(field_id) => Ok(field(record, *field_id)?.is_none()),
+            Node::StartsWith(field_id, prefix) => match field(record, *field_id)? {
+                Some((TypeCode::String, bytes)) => Ok(length_prefixed(&bytes)?.starts_with(prefix)),
+                _ => Ok(false),
+            },
+            Node::Contains(field_id, literal) => {
+                let Some((TypeCode::Array, bytes)) = field(record, *field_id)? else {
+                    return Ok(false);
+                };
+                let mut found = false;
+                for_each_element(bytes, |element| {
+                    if !found {
+                        let (type_code, bytes) = element.unwrap_or((TypeCode::Null, Bytes::new()));
+                        found = literal.cmp_field(type_code, &bytes)? == Some(Ordering::Equal);
+                    }
+                    Ok(())
+                })?;
+                Ok(found)
+            }
+            Node::And(nodes) => {
+                for node in nodes {
+                    if !node.matches(record)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Node::Or(nodes) => {
+                for node in nodes {
+                    if node.matches(record)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Node::Not(node) => Ok(!node.matches(record)?),
+        }
+    }
+}
+
+/// The type code and bytes of a field, or `None` if it's null, deleted or missing
+fn field(record: &ImprintRecord, field_id: u32) -> Result<Option<(TypeCode, Bytes)>, ImprintError> {
+    match record.entry(field_id) {
+        Some(entry) if !matches!(entry.type_code, TypeCode::Null | TypeCode::Tombstone) => {
+            Ok(Some((entry.type_code, record.entry_bytes(entry)?)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Bytes that are equal exactly when the values are, for the types that have them:
+/// fixed-width types other than decimals, and the contents of strings and bytes
+fn index_key(type_code: TypeCode, bytes: &Bytes) -> Result<Option<Bytes>, ImprintError> {
+    match type_code {
+        TypeCode::Decimal => Ok(None),
+        TypeCode::String | TypeCode::Bytes => Ok(Some(bytes.slice_ref(length_prefixed(bytes)?))),
+        _ => Ok(type_code
+            .fixed_width()
+            .map(|width| bytes.slice(..width.min(bytes.len())))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Decimal, IntegerEncoding, writer::test_record};
+
+    fn rows() -> Vec<ImprintRecord> {
+        let row = |encoding, id: i64, name: &str, price: f64, tags: Vec<Value>| {
+            test_record(
+                encoding,
+                vec![
+                    (1, id.into()),
+                    (2, name.into()),
+                    (3, price.into()),
+                    (4, Value::Array(tags)),
+                    (5, Decimal::new(id as i128 * 10, 10, 1).unwrap().into()),
+                ],
+            )
+        };
+        vec![
+            row(
+                IntegerEncoding::Fixed,
+                1,
+                "apple",
+                1.5,
+                vec!["fruit".into()],
+            ),
+            row(IntegerEncoding::Varint, 2, "apricot", 3.0, vec![]),
+            row(
+                IntegerEncoding::Fixed,
+                3,
+                "banana",
+                0.5,
+                vec!["fruit".into(), Value::Null],
+            ),
+            test_record(
+                IntegerEncoding::Fixed,
+                vec![(1, 4i64.into()), (2, Value::Null)],
+            ),
+        ]
+    }
+
+    /// IDs of the rows the predicate matches
+    fn matching(predicate: Predicate) -> Vec<i64> {
+        let compiled = predicate.compile().unwrap();
+        rows()
+            .iter()
+            .filter(|row| compiled.matches(row).unwrap())
+            .map(|row| match row.get_value(1).unwrap() {
+                Some(Value::Int64(id)) => id,
+                other => panic!("unexpected id {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_compare_fields_with_literals() {
+        assert_eq!(matching(Predicate::Eq(1, 2i64.into())), vec![2]);
+        assert_eq!(matching(Predicate::Ne(1, 2i64.into())), vec![1, 3, 4]);
+        assert_eq!(matching(Predicate::Lt(3, 2.0.into())), vec![1, 3]);
+        assert_eq!(matching(Predicate::Ge(1, 3i64.into())), vec![3, 4]);
+        assert_eq!(matching(Predicate::Gt(2, "apricot".into())), vec![3]);
+        assert_eq!(matching(Predicate::Le(2, "apricot".into())), vec![1, 2]);
+
+        // decimals compare numerically, whatever their scale
+        let two = Decimal::new(200, 10, 2).unwrap();
+        assert_eq!(matching(Predicate::Eq(5, two.into())), vec![2]);
+
+        // a literal of another type matches nothing, and neither do nulls
+        assert!(matching(Predicate::Eq(1, 2i32.into())).is_empty());
+        assert_eq!(matching(Predicate::Ne(2, "apple".into())), vec![2, 3]);
+    }
+
+    #[test]
+    fn should_match_sets_prefixes_nulls_and_elements() {
+        // Given IN lists, including values of a type the index can't look up
+        let ids = vec![2i64.into(), 3i64.into(), "2".into()];
+        assert_eq!(matching(Predicate::In(1, ids)), vec![2, 3]);
+        let names = vec!["banana".into(), "cherry".into()];
+        assert_eq!(matching(Predicate::In(2, names)), vec![3]);
+        let decimals = vec![Decimal::new(10, 2, 1).unwrap().into()];
+        assert_eq!(matching(Predicate::In(5, decimals)), vec![1]);
+
+        assert_eq!(matching(Predicate::IsNull(2)), vec![4]);
+        assert_eq!(matching(Predicate::IsNull(3)), vec![4]);
+        assert_eq!(matching(Predicate::StartsWith(2, "ap".into())), vec![1, 2]);
+        assert_eq!(matching(Predicate::Contains(4, "fruit".into())), vec![1, 3]);
+        assert_eq!(matching(Predicate::Contains(4, Value::Null)), vec![3]);
+    }
+
+    #[test]
+    fn should_combine_predicates() {
+        let fruit = Predicate::Contains(4, "fruit".into());
+        let cheap = Predicate::Lt(3, 1.0.into());
+        assert_eq!(matching(fruit.clone().and(cheap.clone())), vec![3]);
+        assert_eq!(matching(fruit.clone().and(!cheap.clone())), vec![1]);
+        assert_eq!(
+            matching(cheap.or(Predicate::Eq(1, 2i64.into()))),
+            vec![2, 3]
+        );
+        assert_eq!(matching(!fruit), vec![2, 4]);
+        assert_eq!(!!Predicate::IsNull(1), Predicate::IsNull(1),);
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+
+use bytes::{Buf, Bytes};
+
+use crate::{
+    error::ImprintError,
+    serde::value_size,
+    types::{DirectoryEntry, ImprintRecord, SchemaId, TypeCode},
+};
+
+/// The fixed field layout of records written without a field directory.
+///
+/// Fields are laid out in the payload in ascending id order, so the directory
+/// can be rebuilt from the schema with a single pass over the payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    fields: Vec<(u32, TypeCode)>,
+}
+
+impl Schema {
+    /// Creates a schema from field ids and their type codes, in any order.
+    pub fn new(mut fields: Vec<(u32, TypeCode)>) -> Result<Self, ImprintError> {
+        fields.sort_unstable_by_key(|&(id, _)| id);
+        if let Some(w) = fields.windows(2).find(|w| w[0].0 == w[1].0) {
+            return Err(ImprintError::SchemaError(format!(
+                "duplicate field id in schema: {}",
+                w[0].0
+            )));
+        }
+        Ok(Self { fields })
+    }
+
+    /// Creates a schema describing the fields of an existing record.
+    pub fn from_record(record: &ImprintRecord) -> Self {
+        Self {
+            fields: record
+                .directory
+                .iter()
+                .map(|e| (e.id, e.type_code))
+                .collect(),
+        }
+    }
+
+    /// The field ids and type codes of this schema, sorted by id
+    pub fn fields(&self) -> &[(u32, TypeCode)] {
+        &self.fields
+    }
+
+    /// Rebuild the field directory of a payload laid out according to this schema
+    pub(crate) fn build_directory(
+        &self,
+        mut payload: Bytes,
+    ) -> Result<Vec<DirectoryEntry>, ImprintError> {
+        let mut directory = Vec::with_capacity(self.fields.len());
+        let mut offset = 0;
+
+        for &(id, type_code) in &self.fields {
+            let size = value_size(type_code, payload.clone())?;
+            directory.push(DirectoryEntry {
+                id,
+                type_code,
+                offset: offset as u32,
+                length: size as u32,
+            });
+            payload.advance(size);
+            offset += size;
+        }
+
+        if payload.has_remaining() {
+            return Err(ImprintError::SchemaError(format!(
+                "{} trailing payload bytes after last schema field",
+                payload.remaining()
+            )));
+        }
+        Ok(directory)
+    }
+}
+
+/// The schema id of a record whose fields were derived by an operation rather than
+/// chosen by a writer, hashing the ids and type codes of its fields so that records
+/// derived the same way share it
+pub(crate) fn derived_schema_id(fieldspace_id: u32, directory: &[DirectoryEntry]) -> SchemaId {
+    let mut fields = Vec::with_capacity(directory.len() * 5);
+    for entry in directory {
+        fields.extend_from_slice(&entry.id.to_le_bytes());
+        fields.push(entry.type_code as u8);
+    }
+    SchemaId {
+        fieldspace_id,
+        schema_hash: crc32c::crc32c(&fields),
+    }
+}
+
+/// A set of schemas used to read records written without a field directory,
+/// along with the names of fields, the symbols of enum fields and the branches
+/// of union fields in each fieldspace.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    schemas: HashMap<SchemaId, Schema>,
+    /// Field IDs keyed by fieldspace ID and field name
+    field_ids: HashMap<(u32, String), u32>,
+    /// Field names keyed by fieldspace ID and field ID
+    field_names: HashMap<(u32, u32), String>,
+    /// Enum symbols indexed by ordinal, keyed by fieldspace ID and field ID
+    enums: HashMap<(u32, u32), Vec<String>>,
+    /// Allowed branch types of union fields, keyed by fieldspace ID and field ID
+    unions: HashMap<(u32, u32), Vec<TypeCode>>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the schema for the given schema ID, replacing any previous one.
+    pub fn register(&mut self, schema_id: SchemaId, schema: Schema) {
+        self.schemas.insert(schema_id, schema);
+    }
+
+    /// Get the schema registered for the given schema ID
+    pub fn get(&self, schema_id: &SchemaId) -> Option<&Schema> {
+        self.schemas.get(schema_id)
+    }
+
+    /// Registers the name of a field, replacing any previous field of that name and
+    /// any previous name of that field. Names are shared by every schema of the
+    /// fieldspace.
+    pub fn register_field_name(&mut self, fieldspace_id: u32, field_id: u32, name: &str) {
+        if let Some(previous) = self
+            .field_ids
+            .insert((fieldspace_id, name.to_string()), field_id)
+        {
+            self.field_names.remove(&(fieldspace_id, previous));
+        }
+        if let Some(previous) = self
+            .field_names
+            .insert((fieldspace_id, field_id), name.to_string())
+            && previous != name
+        {
+            self.field_ids.remove(&(fieldspace_id, previous));
+        }
+    }
+
+    /// Resolve the name of a field to its ID
+    pub fn field_id(&self, fieldspace_id: u32, name: &str) -> Option<u32> {
+        self.field_ids
+            .get(&(fieldspace_id, name.to_string()))
+            .copied()
+    }
+
+    /// Get the registered name of a field
+    pub fn field_name(&self, fieldspace_id: u32, field_id: u32) -> Option<&str> {
+        self.field_names
+            .get(&(fieldspace_id, field_id))
+            .map(String::as_str)
+    }
+
+    /// Registers the symbols of an enum field, in ordinal order, replacing any previous ones.
+    /// Symbols are shared by every schema of the fieldspace.
+    pub fn register_enum(&mut self, fieldspace_id: u32, field_id: u32, symbols: Vec<String>) {
+        self.enums.insert((fieldspace_id, field_id), symbols);
+    }
+
+    /// Resolve the ordinal of an enum field to its symbol
+    pub fn enum_symbol(&self, fieldspace_id: u32, field_id: u32, ordinal: u32) -> Option<&str> {
+        self.enums
+            .get(&(fieldspace_id, field_id))?
+            .get(ordinal as usize)
+            .map(String::as_str)
+    }
+
+    /// Resolve a symbol of an enum field to the ordinal it is written as
+    pub fn enum_ordinal(&self, fieldspace_id: u32, field_id: u32, symbol: &str) -> Option<u32> {
+        self.enums
+            .get(&(fieldspace_id, field_id))?
+            .iter()
+            .position(|s| s == symbol)
+            .and_then(|ordinal| u32::try_from(ordinal).ok())
+    }
+
+    /// Registers the types a union field may hold, replacing any previous ones.
+    pub fn register_union(&mut self, fieldspace_id: u32, field_id: u32, branches: Vec<TypeCode>) {
+        self.unions.insert((fieldspace_id, field_id), branches);
+    }
+
+    /// The types a union field may hold, if declared
+    pub fn union_branches(&self, fieldspace_id: u32, field_id: u32) -> Option<&[TypeCode]> {
+        self.unions
+            .get(&(fieldspace_id, field_id))
+            .map(Vec::as_slice)
+    }
+
+    /// Check that every union field of a record with declared branches holds one of them
+    pub fn check_unions(&self, record: &ImprintRecord) -> Result<(), ImprintError> {
+        let fieldspace_id = record.header.schema_id.fieldspace_id;
+        for entry in &record.directory {
+            let Some(branches) = self.union_branches(fieldspace_id, entry.id) else {
+                continue;
+            };
+            let branch = record.get_value(entry.id)?.and_then(|v| v.union_branch());
+            if !branch.is_some_and(|branch| branches.contains(&branch)) {
+                return Err(ImprintError::SchemaError(format!(
+                    "field {} holds {:?}, which is not one of its union branches {:?}",
+                    entry.id,
+                    branch.unwrap_or(entry.type_code),
+                    branches
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a record, rebuilding its field directory from the registered
+    /// schema when the record was written without one. Records that carry a
+    /// directory are returned as read.
+    pub fn read(&self, bytes: Bytes) -> Result<(ImprintRecord, usize), ImprintError> {
+        let (mut record, size) = ImprintRecord::read_as_written(bytes)?;
+        if record.header.flags.has_field_directory() {
+            return Ok((record, size));
+        }
+
+        let schema_id = record.header.schema_id;
+        let schema = self.get(&schema_id).ok_or_else(|| {
+            ImprintError::SchemaError(format!(
+                "no schema registered for fieldspace {} with hash {:#x}",
+                schema_id.fieldspace_id, schema_id.schema_hash
+            ))
+        })?;
+        record.directory = schema.build_directory(record.payload.get()?.clone())?;
+        Ok((record, size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Flags, ImprintWriter, Value,
+        serde::{Read, Write},
+    };
+    use bytes::BytesMut;
+
+    const SCHEMA_ID: SchemaId = SchemaId {
+        fieldspace_id: 1,
+        schema_hash: 0xdeadbeef,
+    };
+
+    fn create_directoryless_record() -> ImprintRecord {
+        let mut writer = ImprintWriter::new(SCHEMA_ID).unwrap();
+        writer.set_field_directory(false);
+        writer.add_field(1, 42.into()).unwrap();
+        writer.add_field(2, "hello".into()).unwrap();
+        writer.add_field(3, Value::Null).unwrap();
+        writer.add_field(4, vec!["a", "bc"].into()).unwrap();
+        writer.add_field(5, 7i64.into()).unwrap();
+        writer.build().unwrap()
+    }
+
+    fn create_registry() -> SchemaRegistry {
+        let mut registry = SchemaRegistry::new();
+        let schema = Schema::new(vec![
+            (5, TypeCode::Int64),
+            (1, TypeCode::Int32),
+            (2, TypeCode::String),
+            (3, TypeCode::Null),
+            (4, TypeCode::Array),
+        ])
+        .unwrap();
+        registry.register(SCHEMA_ID, schema);
+        registry
+    }
+
+    #[test]
+    fn should_omit_directory_when_writing() {
+        // Given the same record with and without a directory
+        let record = create_directoryless_record();
+        let mut with_directory = record.clone();
+        with_directory.header.flags = with_directory
+            .header
+            .flags
+            .with(Flags::FIELD_DIRECTORY, true);
+
+        // When serializing both
+        let mut buf = BytesMut::new();
+        record.write(&mut buf).unwrap();
+        let mut full_buf = BytesMut::new();
+        with_directory.write(&mut full_buf).unwrap();
+
+        // Then the directory-less record should be smaller by the whole directory
+        assert_eq!(full_buf.len() - buf.len(), 1 + 5 * 9);
+    }
+
+    #[test]
+    fn should_rebuild_directory_from_schema() {
+        // Given a serialized record without a directory
+        let record = create_directoryless_record();
+        let mut buf = BytesMut::new();
+        record.write(&mut buf).unwrap();
+
+        // When reading it through a registry with its schema
+        let (read, size) = create_registry().read(buf.clone().freeze()).unwrap();
+
+        // Then every field should be accessible
+        assert_eq!(size, buf.len());
+        assert_eq!(read.directory, record.directory);
+        assert_eq!(read.get_value(1).unwrap(), Some(42.into()));
+        assert_eq!(read.get_value(2).unwrap(), Some("hello".into()));
+        assert_eq!(read.get_value(3).unwrap(), Some(Value::Null));
+        assert_eq!(read.get_value(4).unwrap(), Some(vec!["a", "bc"].into()));
+        assert_eq!(read.get_value(5).unwrap(), Some(7i64.into()));
+    }
+
+    #[test]
+    fn should_reject_plain_reads_of_directoryless_records() {
+        // Given a serialized record without a directory
+        let mut buf = BytesMut::new();
+        create_directoryless_record().write(&mut buf).unwrap();
+
+        // When reading it without a registry
+        // Then it should fail rather than return a record without fields
+        assert!(matches!(
+            ImprintRecord::read(buf.freeze()),
+            Err(ImprintError::SchemaError(_))
+        ));
+    }
+
+    #[test]
+    fn should_pass_through_records_with_directory() {
+        // Given a record with a directory and an empty registry
+        let mut writer = ImprintWriter::new(SCHEMA_ID).unwrap();
+        writer.add_field(1, 42.into()).unwrap();
+        let record = writer.build().unwrap();
+        let mut buf = BytesMut::new();
+        record.write(&mut buf).unwrap();
+
+        // When reading it
+        let (read, _) = SchemaRegistry::new().read(buf.freeze()).unwrap();
+
+        // Then it should be read as is
+        assert_eq!(read, record);
+    }
+
+    #[test]
+    fn should_reject_unregistered_or_mismatched_schema() {
+        // Given a serialized record without a directory
+        let mut buf = BytesMut::new();
+        create_directoryless_record().write(&mut buf).unwrap();
+
+        // When reading it without a registered schema
+        // Then it should return a schema error
+        assert!(matches!(
+            SchemaRegistry::new().read(buf.clone().freeze()),
+            Err(ImprintError::SchemaError(_))
+        ));
+
+        // And when reading it with a schema that doesn't cover the payload
+        let mut registry = SchemaRegistry::new();
+        registry.register(
+            SCHEMA_ID,
+            Schema::new(vec![(1, TypeCode::Int32), (2, TypeCode::String)]).unwrap(),
+        );
+
+        // Then it should return a schema error
+        assert!(matches!(
+            registry.read(buf.freeze()),
+            Err(ImprintError::SchemaError(_))
+        ));
+    }
+
+    #[test]
+    fn should_resolve_enum_symbols_within_fieldspace() {
+        // Given enum symbols registered for a field of a fieldspace
+        let mut registry = SchemaRegistry::new();
+        registry.register_enum(1, 7, vec!["RED".into(), "GREEN".into()]);
+
+        // And a record holding one of them as an ordinal
+        let mut writer = ImprintWriter::new(SCHEMA_ID).unwrap();
+        let ordinal = registry.enum_ordinal(1, 7, "GREEN").unwrap();
+        writer.add_field(7, Value::Enum(ordinal)).unwrap();
+        let record = writer.build().unwrap();
+
+        // When resolving the ordinal read back from the record
+        let Some(Value::Enum(read)) = record.get_value(7).unwrap() else {
+            panic!("expected an enum value");
+        };
+
+        // Then it should map back to its symbol, and only within the fieldspace
+        assert_eq!(registry.enum_symbol(1, 7, read), Some("GREEN"));
+        assert_eq!(registry.enum_symbol(1, 7, 2), None);
+        assert_eq!(registry.enum_symbol(2, 7, read), None);
+        assert_eq!(registry.enum_ordinal(1, 7, "BLUE"), None);
+    }
+
+    #[test]
+    fn should_check_union_branches() {
+        // Given a union field declared as either a string or an int64
+        let mut registry = SchemaRegistry::new();
+        registry.register_union(1, 3, vec![TypeCode::String, TypeCode::Int64]);
+
+        let record_with = |value: Value| {
+            let mut writer = ImprintWriter::new(SCHEMA_ID).unwrap();
+            writer.add_field(3, value).unwrap();
+            writer.build().unwrap()
+        };
+
+        // When checking records holding each branch
+        // Then declared branches should pass and anything else should fail
+        let legacy_id = record_with(Value::Union(Box::new("C-42".into())));
+        assert!(registry.check_unions(&legacy_id).is_ok());
+        let id = record_with(Value::Union(Box::new(42i64.into())));
+        assert!(registry.check_unions(&id).is_ok());
+        assert!(matches!(
+            registry.check_unions(&record_with(Value::Union(Box::new(42i32.into())))),
+            Err(ImprintError::SchemaError(_))
+        ));
+        assert!(matches!(
+            registry.check_unions(&record_with("not a union".into())),
+            Err(ImprintError::SchemaError(_))
+        ));
+
+        // And readers should be able to match on the active branch
+        match id.get_value(3).unwrap() {
+            Some(Value::Union(inner)) => assert_eq!(*inner, Value::Int64(42)),
+            other => panic!("expected a union, got {:?}", other),
+        }
+        assert_eq!(
+            legacy_id.get_value(3).unwrap().unwrap().union_branch(),
+            Some(TypeCode::String)
+        );
+    }
+
+    #[test]
+    fn should_reject_duplicate_schema_fields() {
+        assert!(matches!(
+            Schema::new(vec![(1, TypeCode::Int32), (1, TypeCode::String)]),
+            Err(ImprintError::SchemaError(_))
+        ));
+    }
+}
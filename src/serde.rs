@@ -4,14 +4,25 @@ use bytes::{Buf, BufMut, Bytes, BytesMut};
 
 use crate::{
     MAGIC, VERSION,
+    compression::Compression,
+    decimal::Decimal,
     error::ImprintError,
-    types::{DirectoryEntry, Flags, Header, ImprintRecord, MapKey, SchemaId, TypeCode, Value},
+    temporal::{self, Timestamp},
+    types::{
+        DirectoryEntry, Flags, Header, ImprintRecord, MapKey, Payload, SchemaId, TypeCode, Value,
+    },
     varint,
 };
 
 const HEADER_BYTES: usize = 15;
 const DIR_COUNT_BYTES: usize = 5;
 const DIR_ENTRY_BYTES: usize = 9;
+const CHECKSUM_BYTES: usize = 4;
+
+/// Set on the element type of an array whose elements may be null
+const NULLABLE_ELEMENTS: u8 = 0x80;
+/// Element type of an array whose elements each carry their own type code
+const MIXED_ELEMENTS: u8 = 0x7F;
 
 /// A trait for types that can be written to a byte buffer
 pub trait Write {
@@ -57,47 +68,81 @@ impl Write for Value {
                 Ok(())
             }
             Self::Bytes(v) => {
-                varint::encode(v.len() as u32, buf);
+                varint::encode_len(v.len(), buf)?;
                 buf.put_slice(v);
                 Ok(())
             }
             Self::String(v) => {
                 let bytes = v.as_bytes();
-                varint::encode(bytes.len() as u32, buf);
+                varint::encode_len(bytes.len(), buf)?;
                 buf.put_slice(bytes);
                 Ok(())
             }
             Self::Array(v) => {
-                varint::encode(v.len() as u32, buf);
+                varint::encode_len(v.len(), buf)?;
                 if v.is_empty() {
                     return Ok(());
                 }
 
-                let type_code = v[0].type_code();
-                buf.put_u8(type_code as u8);
-                for value in v {
-                    if value.type_code() != type_code {
-                        return Err(ImprintError::SchemaError(format!(
-                            "array elements must have same type code: {:?} != {:?}",
-                            value.type_code(),
-                            type_code
-                        )));
+                if v.contains(&Value::Tombstone) {
+                    return Err(nested_tombstone());
+                }
+                match ArrayLayout::of(v) {
+                    ArrayLayout::Homogeneous(type_code) => {
+                        buf.put_u8(type_code as u8);
+                        let fixed_size = write_fixed_size(&v[0], buf)?;
+                        for value in v {
+                            check_fixed_size(value, fixed_size)?;
+                            value.write(buf)?;
+                        }
+                    }
+                    ArrayLayout::Nullable(type_code) => {
+                        buf.put_u8(type_code as u8 | NULLABLE_ELEMENTS);
+                        let mut validity = vec![0u8; v.len().div_ceil(8)];
+                        for (i, value) in v.iter().enumerate() {
+                            if *value != Value::Null {
+                                validity[i / 8] |= 1 << (i % 8);
+                            }
+                        }
+                        buf.put_slice(&validity);
+
+                        let present = v.iter().filter(|value| **value != Value::Null);
+                        let first = present.clone().next().unwrap();
+                        let fixed_size = write_fixed_size(first, buf)?;
+                        for value in present {
+                            check_fixed_size(value, fixed_size)?;
+                            value.write(buf)?;
+                        }
+                    }
+                    ArrayLayout::Mixed => {
+                        buf.put_u8(MIXED_ELEMENTS);
+                        for value in v {
+                            write_tagged(value, buf)?;
+                        }
                     }
-                    value.write(buf)?;
                 }
                 Ok(())
             }
             Self::Map(m) => {
-                varint::encode(m.len() as u32, buf);
+                varint::encode_len(m.len(), buf)?;
                 if m.is_empty() {
                     return Ok(());
                 }
 
-                let key_type_code = m.keys().next().unwrap().type_code();
-                let value_type_code = m.values().next().unwrap().type_code();
+                // entries are written in key order so that equal maps encode to equal bytes
+                let mut entries: Vec<_> = m.iter().collect();
+                entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+                let (first_key, first_value) = entries[0];
+                let key_type_code = first_key.type_code();
+                let value_type_code = first_value.type_code();
+                if value_type_code == TypeCode::Tombstone {
+                    return Err(nested_tombstone());
+                }
                 buf.put_u8(key_type_code as u8);
                 buf.put_u8(value_type_code as u8);
-                for (key, value) in m {
+                let fixed_size = write_fixed_size(first_value, buf)?;
+                for (key, value) in entries {
                     if key.type_code() != key_type_code {
                         return Err(ImprintError::SchemaError(format!(
                             "map keys must have same type code: {:?} != {:?}",
@@ -112,19 +157,175 @@ impl Write for Value {
                             value_type_code
                         )));
                     }
+                    check_fixed_size(value, fixed_size)?;
                     key.write(buf)?;
                     value.write(buf)?;
                 }
                 Ok(())
             }
             Self::Row(v) => v.write(buf),
+            Self::Timestamp(v) => {
+                buf.put_u8(v.metadata());
+                buf.put_i64_le(v.value);
+                Ok(())
+            }
+            Self::Date(v) => {
+                buf.put_i32_le(*v);
+                Ok(())
+            }
+            Self::Time(v) => {
+                buf.put_i64_le(temporal::check_time_of_day(*v)?);
+                Ok(())
+            }
+            Self::Decimal(v) => {
+                buf.put_u8(v.precision());
+                buf.put_u8(v.scale());
+                buf.put_i128_le(v.unscaled());
+                Ok(())
+            }
+            Self::Uuid(v) => {
+                buf.put_slice(v);
+                Ok(())
+            }
+            Self::FixedBytes(v) => {
+                // the size comes from the directory entry or the enclosing array or map
+                buf.put_slice(v);
+                Ok(())
+            }
+            Self::Enum(v) => {
+                varint::encode(*v, buf);
+                Ok(())
+            }
+            Self::Int8(v) => {
+                buf.put_i8(*v);
+                Ok(())
+            }
+            Self::Int16(v) => {
+                buf.put_i16_le(*v);
+                Ok(())
+            }
+            Self::UInt8(v) => {
+                buf.put_u8(*v);
+                Ok(())
+            }
+            Self::UInt16(v) => {
+                buf.put_u16_le(*v);
+                Ok(())
+            }
+            Self::UInt32(v) => {
+                buf.put_u32_le(*v);
+                Ok(())
+            }
+            Self::UInt64(v) => {
+                buf.put_u64_le(*v);
+                Ok(())
+            }
+            Self::Union(v) => write_tagged(v, buf),
+            Self::Tombstone => Ok(()),
         }
     }
 }
 
+impl Value {
+    /// The varint type code this value can alternatively be written as, if it
+    /// is a 32 or 64-bit integer
+    pub(crate) fn varint_type_code(&self) -> Option<TypeCode> {
+        match self {
+            Self::Int32(_) => Some(TypeCode::VarInt32),
+            Self::Int64(_) => Some(TypeCode::VarInt64),
+            Self::UInt32(_) => Some(TypeCode::VarUInt32),
+            Self::UInt64(_) => Some(TypeCode::VarUInt64),
+            _ => None,
+        }
+    }
+
+    /// Write this value in the encoding of `type_code`, which must be either
+    /// its own type code or its varint one
+    pub(crate) fn write_as(
+        &self,
+        type_code: TypeCode,
+        buf: &mut BytesMut,
+    ) -> Result<(), ImprintError> {
+        match (self, type_code) {
+            (Self::Int32(v), TypeCode::VarInt32) => varint::encode(varint::zigzag_encode(*v), buf),
+            (Self::Int64(v), TypeCode::VarInt64) => varint::encode_i64(*v, buf),
+            (Self::UInt32(v), TypeCode::VarUInt32) => varint::encode(*v, buf),
+            (Self::UInt64(v), TypeCode::VarUInt64) => varint::encode_u64(*v, buf),
+            _ if type_code == self.type_code() => return self.write(buf),
+            _ => {
+                return Err(ImprintError::TypeMismatch {
+                    expected: type_code,
+                    actual: self.type_code(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write the size shared by the elements of an array or map of fixed-size
+/// bytes after its type codes, returning it so each element can be checked
+fn write_fixed_size(first: &Value, buf: &mut BytesMut) -> Result<Option<usize>, ImprintError> {
+    match first {
+        Value::FixedBytes(v) => {
+            varint::encode_len(v.len(), buf)?;
+            Ok(Some(v.len()))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn check_fixed_size(value: &Value, fixed_size: Option<usize>) -> Result<(), ImprintError> {
+    match (value, fixed_size) {
+        (Value::FixedBytes(v), Some(size)) if v.len() != size => {
+            Err(ImprintError::SchemaError(format!(
+                "fixed bytes must all have the same size: {} != {}",
+                v.len(),
+                size
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Read the element size that follows the type code of fixed-size bytes in an array or map
+fn read_fixed_size(
+    type_code: TypeCode,
+    bytes: &mut Bytes,
+) -> Result<(Option<usize>, usize), ImprintError> {
+    if type_code != TypeCode::FixedBytes {
+        return Ok((None, 0));
+    }
+    let (size, size_len) = varint::decode(bytes.clone())?;
+    bytes.advance(size_len);
+    Ok((Some(size as usize), size_len))
+}
+
+/// Read an array or map element, bounding fixed-size bytes to their shared size
+fn read_element(
+    type_code: TypeCode,
+    fixed_size: Option<usize>,
+    bytes: Bytes,
+) -> Result<(Value, usize), ImprintError> {
+    let Some(size) = fixed_size else {
+        return Value::read(type_code, bytes);
+    };
+    if bytes.remaining() < size {
+        return Err(ImprintError::BufferUnderflow {
+            needed: size,
+            available: bytes.remaining(),
+        });
+    }
+    Value::read(type_code, bytes.slice(..size))
+}
+
 impl Write for MapKey {
     fn write(&self, buf: &mut BytesMut) -> Result<(), ImprintError> {
         match self {
+            MapKey::Bool(b) => {
+                buf.put_u8(*b as u8);
+                Ok(())
+            }
             MapKey::Int32(i) => {
                 buf.put_i32_le(*i);
                 Ok(())
@@ -134,16 +335,44 @@ impl Write for MapKey {
                 Ok(())
             }
             MapKey::Bytes(b) => {
-                varint::encode(b.len() as u32, buf);
+                varint::encode_len(b.len(), buf)?;
                 buf.put_slice(b);
                 Ok(())
             }
             MapKey::String(s) => {
                 let bytes = s.as_bytes();
-                varint::encode(bytes.len() as u32, buf);
+                varint::encode_len(bytes.len(), buf)?;
                 buf.put_slice(bytes);
                 Ok(())
             }
+            MapKey::Uuid(u) => {
+                buf.put_slice(u);
+                Ok(())
+            }
+            MapKey::Int8(i) => {
+                buf.put_i8(*i);
+                Ok(())
+            }
+            MapKey::Int16(i) => {
+                buf.put_i16_le(*i);
+                Ok(())
+            }
+            MapKey::UInt8(i) => {
+                buf.put_u8(*i);
+                Ok(())
+            }
+            MapKey::UInt16(i) => {
+                buf.put_u16_le(*i);
+                Ok(())
+            }
+            MapKey::UInt32(i) => {
+                buf.put_u32_le(*i);
+                Ok(())
+            }
+            MapKey::UInt64(i) => {
+                buf.put_u64_le(*i);
+                Ok(())
+            }
         }
     }
 }
@@ -154,6 +383,7 @@ impl ValueRead for Value {
 
         let value = match type_code {
             TypeCode::Null => Value::Null,
+            TypeCode::Tombstone => Value::Tombstone,
             TypeCode::Bool => {
                 if !bytes.has_remaining() {
                     return Err(ImprintError::BufferUnderflow {
@@ -247,24 +477,16 @@ impl ValueRead for Value {
                 s.into()
             }
             TypeCode::Array => {
-                let (len, len_size) = varint::decode(bytes.clone())?;
-                bytes.advance(len_size);
-                bytes_read += len_size;
-
-                if len == 0 {
-                    return Ok((Value::Array(vec![]), bytes_read));
-                }
-
-                let element_type = TypeCode::try_from(bytes.get_u8())?;
-                bytes_read += 1;
-
-                let mut values = Vec::with_capacity(len as usize);
-                for _ in 0..len {
-                    let (value, value_size) = Self::read(element_type, bytes.clone())?;
-                    bytes.advance(value_size);
-                    bytes_read += value_size;
+                let mut values = Vec::new();
+                bytes_read += walk_array(bytes, |element, bytes| {
+                    let Some((type_code, fixed_size)) = element else {
+                        values.push(Value::Null);
+                        return Ok(0);
+                    };
+                    let (value, value_size) = read_element(type_code, fixed_size, bytes)?;
                     values.push(value);
-                }
+                    Ok(value_size)
+                })?;
                 values.into()
             }
             TypeCode::Map => {
@@ -276,11 +498,13 @@ impl ValueRead for Value {
                     return Ok((Value::Map(HashMap::new()), bytes_read));
                 }
 
-                let key_type = TypeCode::try_from(bytes.get_u8())?;
+                let key_type = nested_type_code(bytes.get_u8())?;
                 bytes_read += 1;
 
-                let value_type = TypeCode::try_from(bytes.get_u8())?;
+                let value_type = nested_type_code(bytes.get_u8())?;
                 bytes_read += 1;
+                let (fixed_size, fixed_size_len) = read_fixed_size(value_type, &mut bytes)?;
+                bytes_read += fixed_size_len;
 
                 let mut map = HashMap::with_capacity(len as usize);
                 for _ in 0..len {
@@ -288,7 +512,7 @@ impl ValueRead for Value {
                     bytes.advance(key_size);
                     bytes_read += key_size;
 
-                    let (value, value_size) = Self::read(value_type, bytes.clone())?;
+                    let (value, value_size) = read_element(value_type, fixed_size, bytes.clone())?;
                     bytes.advance(value_size);
                     bytes_read += value_size;
 
@@ -301,6 +525,166 @@ impl ValueRead for Value {
                 bytes_read += size;
                 record.into()
             }
+            TypeCode::Timestamp => {
+                if bytes.remaining() < 9 {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 9,
+                        available: bytes.remaining(),
+                    });
+                }
+                let metadata = bytes.get_u8();
+                let v = bytes.get_i64_le();
+                bytes_read += 9;
+                Timestamp::from_metadata(metadata, v)?.into()
+            }
+            TypeCode::Date => {
+                if bytes.remaining() < 4 {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 4,
+                        available: bytes.remaining(),
+                    });
+                }
+                let v = bytes.get_i32_le();
+                bytes_read += 4;
+                Value::Date(v)
+            }
+            TypeCode::Time => {
+                if bytes.remaining() < 8 {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 8,
+                        available: bytes.remaining(),
+                    });
+                }
+                let v = bytes.get_i64_le();
+                bytes_read += 8;
+                Value::Time(temporal::check_time_of_day(v)?)
+            }
+            TypeCode::Decimal => {
+                if bytes.remaining() < 18 {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 18,
+                        available: bytes.remaining(),
+                    });
+                }
+                let precision = bytes.get_u8();
+                let scale = bytes.get_u8();
+                let unscaled = bytes.get_i128_le();
+                bytes_read += 18;
+                Decimal::new(unscaled, precision, scale)?.into()
+            }
+            TypeCode::Uuid => {
+                if bytes.remaining() < 16 {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 16,
+                        available: bytes.remaining(),
+                    });
+                }
+                let mut v = [0; 16];
+                bytes.copy_to_slice(&mut v);
+                bytes_read += 16;
+                Value::Uuid(v)
+            }
+            TypeCode::FixedBytes => {
+                // callers bound the bytes to the value's size
+                bytes_read += bytes.remaining();
+                Value::FixedBytes(bytes.to_vec())
+            }
+            TypeCode::Enum => {
+                let (v, v_size) = varint::decode(bytes.clone())?;
+                bytes_read += v_size;
+                Value::Enum(v)
+            }
+            TypeCode::Int8 => {
+                if bytes.remaining() < 1 {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 1,
+                        available: bytes.remaining(),
+                    });
+                }
+                let v = bytes.get_i8();
+                bytes_read += 1;
+                v.into()
+            }
+            TypeCode::Int16 => {
+                if bytes.remaining() < 2 {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 2,
+                        available: bytes.remaining(),
+                    });
+                }
+                let v = bytes.get_i16_le();
+                bytes_read += 2;
+                v.into()
+            }
+            TypeCode::UInt8 => {
+                if bytes.remaining() < 1 {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 1,
+                        available: bytes.remaining(),
+                    });
+                }
+                let v = bytes.get_u8();
+                bytes_read += 1;
+                Value::UInt8(v)
+            }
+            TypeCode::UInt16 => {
+                if bytes.remaining() < 2 {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 2,
+                        available: bytes.remaining(),
+                    });
+                }
+                let v = bytes.get_u16_le();
+                bytes_read += 2;
+                v.into()
+            }
+            TypeCode::UInt32 => {
+                if bytes.remaining() < 4 {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 4,
+                        available: bytes.remaining(),
+                    });
+                }
+                let v = bytes.get_u32_le();
+                bytes_read += 4;
+                v.into()
+            }
+            TypeCode::UInt64 => {
+                if bytes.remaining() < 8 {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 8,
+                        available: bytes.remaining(),
+                    });
+                }
+                let v = bytes.get_u64_le();
+                bytes_read += 8;
+                v.into()
+            }
+            TypeCode::VarInt32 => {
+                let (v, v_size) = varint::decode(bytes)?;
+                bytes_read += v_size;
+                Value::Int32(varint::zigzag_decode(v))
+            }
+            TypeCode::VarInt64 => {
+                let (v, v_size) = varint::decode_i64(bytes)?;
+                bytes_read += v_size;
+                Value::Int64(v)
+            }
+            TypeCode::VarUInt32 => {
+                let (v, v_size) = varint::decode(bytes)?;
+                bytes_read += v_size;
+                Value::UInt32(v)
+            }
+            TypeCode::VarUInt64 => {
+                let (v, v_size) = varint::decode_u64(bytes)?;
+                bytes_read += v_size;
+                Value::UInt64(v)
+            }
+            TypeCode::Union => {
+                let (v, v_size) = read_tagged(bytes)?;
+                bytes_read += v_size;
+                Value::Union(Box::new(v))
+            }
         };
         Ok((value, bytes_read))
     }
@@ -313,6 +697,276 @@ impl ValueRead for MapKey {
     }
 }
 
+fn nested_tombstone() -> ImprintError {
+    ImprintError::SchemaError("tombstones can only be the value of a field".into())
+}
+
+/// Parse the type code of an array element, map key or value or union branch
+fn nested_type_code(b: u8) -> Result<TypeCode, ImprintError> {
+    match TypeCode::try_from(b)? {
+        TypeCode::Tombstone => Err(nested_tombstone()),
+        type_code => Ok(type_code),
+    }
+}
+
+/// Write a value preceded by its own type code, and by its size if it is fixed-size bytes
+fn write_tagged(value: &Value, buf: &mut BytesMut) -> Result<(), ImprintError> {
+    if *value == Value::Tombstone {
+        return Err(nested_tombstone());
+    }
+    buf.put_u8(value.type_code() as u8);
+    write_fixed_size(value, buf)?;
+    value.write(buf)
+}
+
+/// Read the type code and fixed size that precede a value written by [`write_tagged`]
+fn read_tag(bytes: &mut Bytes) -> Result<(TypeCode, Option<usize>, usize), ImprintError> {
+    if !bytes.has_remaining() {
+        return Err(ImprintError::BufferUnderflow {
+            needed: 1,
+            available: 0,
+        });
+    }
+    let type_code = nested_type_code(bytes.get_u8())?;
+    let (fixed_size, fixed_size_len) = read_fixed_size(type_code, bytes)?;
+    Ok((type_code, fixed_size, 1 + fixed_size_len))
+}
+
+/// Read a value written by [`write_tagged`]
+fn read_tagged(mut bytes: Bytes) -> Result<(Value, usize), ImprintError> {
+    let (type_code, fixed_size, tag_size) = read_tag(&mut bytes)?;
+    let (value, value_size) = read_element(type_code, fixed_size, bytes)?;
+    Ok((value, tag_size + value_size))
+}
+
+/// Size of a value written by [`write_tagged`] without deserializing it
+fn tagged_size(mut bytes: Bytes) -> Result<usize, ImprintError> {
+    let (type_code, fixed_size, tag_size) = read_tag(&mut bytes)?;
+    Ok(tag_size + element_size(type_code, fixed_size, bytes)?)
+}
+
+/// Size of an array or map element without deserializing it
+fn element_size(
+    type_code: TypeCode,
+    fixed_size: Option<usize>,
+    bytes: Bytes,
+) -> Result<usize, ImprintError> {
+    match fixed_size {
+        Some(size) if bytes.remaining() < size => Err(ImprintError::BufferUnderflow {
+            needed: size,
+            available: bytes.remaining(),
+        }),
+        Some(size) => Ok(size),
+        None => value_size(type_code, bytes),
+    }
+}
+
+/// How the elements of a non-empty array are laid out after its length
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArrayLayout {
+    /// Every element has the given type code
+    Homogeneous(TypeCode),
+    /// Elements are null or of the given type code, with a validity bitmap
+    /// followed by the values of the non-null elements
+    Nullable(TypeCode),
+    /// Every element is preceded by its own type code
+    Mixed,
+}
+
+impl ArrayLayout {
+    /// The most compact layout that can hold the given elements
+    fn of(values: &[Value]) -> Self {
+        let mut present = values
+            .iter()
+            .map(Value::type_code)
+            .filter(|&type_code| type_code != TypeCode::Null);
+        let Some(type_code) = present.next() else {
+            return Self::Homogeneous(TypeCode::Null);
+        };
+        if !present.all(|other| other == type_code) {
+            Self::Mixed
+        } else if values.contains(&Value::Null) {
+            Self::Nullable(type_code)
+        } else {
+            Self::Homogeneous(type_code)
+        }
+    }
+}
+
+/// Walk the elements of a serialized array, calling `visit` with the type code
+/// and shared fixed size of each element, or `None` for the nulls of a nullable
+/// array, along with the bytes it starts at. `visit` returns the element's size
+/// and the size of the whole array is returned.
+fn walk_array(
+    mut bytes: Bytes,
+    mut visit: impl FnMut(Option<(TypeCode, Option<usize>)>, Bytes) -> Result<usize, ImprintError>,
+) -> Result<usize, ImprintError> {
+    let (len, len_size) = varint::decode(bytes.clone())?;
+    bytes.advance(len_size);
+    let mut size = len_size;
+    if len == 0 {
+        return Ok(size);
+    }
+
+    if !bytes.has_remaining() {
+        return Err(ImprintError::BufferUnderflow {
+            needed: 1,
+            available: 0,
+        });
+    }
+    let layout = match bytes.get_u8() {
+        MIXED_ELEMENTS => ArrayLayout::Mixed,
+        b if b & NULLABLE_ELEMENTS != 0 => {
+            ArrayLayout::Nullable(nested_type_code(b & !NULLABLE_ELEMENTS)?)
+        }
+        b => ArrayLayout::Homogeneous(nested_type_code(b)?),
+    };
+    size += 1;
+
+    let validity = match layout {
+        ArrayLayout::Nullable(_) => {
+            let validity_size = (len as usize).div_ceil(8);
+            if bytes.remaining() < validity_size {
+                return Err(ImprintError::BufferUnderflow {
+                    needed: validity_size,
+                    available: bytes.remaining(),
+                });
+            }
+            size += validity_size;
+            Some(bytes.split_to(validity_size))
+        }
+        _ => None,
+    };
+
+    let mut shared = None;
+    if let ArrayLayout::Homogeneous(type_code) | ArrayLayout::Nullable(type_code) = layout {
+        let (fixed_size, fixed_size_len) = read_fixed_size(type_code, &mut bytes)?;
+        size += fixed_size_len;
+        shared = Some((type_code, fixed_size));
+    }
+
+    for i in 0..len as usize {
+        let element = match (&validity, shared) {
+            (Some(validity), _) if validity[i / 8] & (1 << (i % 8)) == 0 => None,
+            (_, Some(shared)) => Some(shared),
+            _ => {
+                let (type_code, fixed_size, tag_size) = read_tag(&mut bytes)?;
+                size += tag_size;
+                Some((type_code, fixed_size))
+            }
+        };
+        let element_size = visit(element, bytes.clone())?;
+        bytes.advance(element_size);
+        size += element_size;
+    }
+    Ok(size)
+}
+
+/// Visit the type code and bytes of each element of an encoded array without
+/// decoding it, with `None` for the nulls of a nullable array
+pub(crate) fn for_each_element(
+    bytes: Bytes,
+    mut visit: impl FnMut(Option<(TypeCode, Bytes)>) -> Result<(), ImprintError>,
+) -> Result<(), ImprintError> {
+    walk_array(bytes, |element, bytes| match element {
+        None => visit(None).map(|_| 0),
+        Some((type_code, fixed_size)) => {
+            let size = element_size(type_code, fixed_size, bytes.clone())?;
+            visit(Some((type_code, bytes.slice(..size))))?;
+            Ok(size)
+        }
+    })?;
+    Ok(())
+}
+
+/// Compute the encoded size of a value with a known type code without deserializing it
+pub(crate) fn value_size(type_code: TypeCode, mut bytes: Bytes) -> Result<usize, ImprintError> {
+    let size = match type_code {
+        TypeCode::Null | TypeCode::Tombstone => 0,
+        TypeCode::Bool
+        | TypeCode::Int32
+        | TypeCode::Int64
+        | TypeCode::Float32
+        | TypeCode::Float64
+        | TypeCode::Timestamp
+        | TypeCode::Date
+        | TypeCode::Time
+        | TypeCode::Decimal
+        | TypeCode::Uuid
+        | TypeCode::Int8
+        | TypeCode::Int16
+        | TypeCode::UInt8
+        | TypeCode::UInt16
+        | TypeCode::UInt32
+        | TypeCode::UInt64 => type_code.fixed_width().unwrap(),
+        TypeCode::Bytes | TypeCode::String => {
+            let (len, len_size) = varint::decode(bytes.clone())?;
+            len_size + len as usize
+        }
+        TypeCode::Enum | TypeCode::VarInt32 | TypeCode::VarUInt32 => {
+            varint::decode(bytes.clone())?.1
+        }
+        TypeCode::VarInt64 | TypeCode::VarUInt64 => varint::decode_u64(bytes.clone())?.1,
+        TypeCode::FixedBytes => {
+            return Err(ImprintError::SchemaError(
+                "the size of top-level fixed bytes is only known from a field directory".into(),
+            ));
+        }
+        TypeCode::Union => return tagged_size(bytes),
+        TypeCode::Array => {
+            return walk_array(bytes, |element, bytes| match element {
+                Some((type_code, fixed_size)) => element_size(type_code, fixed_size, bytes),
+                None => Ok(0),
+            });
+        }
+        TypeCode::Map => {
+            // elements are consumed as we go, so any underflow surfaces from the elements
+            let (len, len_size) = varint::decode(bytes.clone())?;
+            bytes.advance(len_size);
+            let mut size = len_size;
+            if len == 0 {
+                return Ok(size);
+            }
+
+            let mut element_types = Vec::with_capacity(2);
+            for _ in 0..2 {
+                if !bytes.has_remaining() {
+                    return Err(ImprintError::BufferUnderflow {
+                        needed: 1,
+                        available: 0,
+                    });
+                }
+                element_types.push(nested_type_code(bytes.get_u8())?);
+                size += 1;
+            }
+            let mut fixed_sizes = Vec::with_capacity(2);
+            for &element_type in &element_types {
+                let (fixed_size, fixed_size_len) = read_fixed_size(element_type, &mut bytes)?;
+                fixed_sizes.push(fixed_size);
+                size += fixed_size_len;
+            }
+
+            for _ in 0..len {
+                for (&element_type, &fixed_size) in element_types.iter().zip(&fixed_sizes) {
+                    let element_size = element_size(element_type, fixed_size, bytes.clone())?;
+                    bytes.advance(element_size);
+                    size += element_size;
+                }
+            }
+            return Ok(size);
+        }
+        TypeCode::Row => ImprintRecord::read(bytes.clone())?.1,
+    };
+
+    if bytes.remaining() < size {
+        return Err(ImprintError::BufferUnderflow {
+            needed: size,
+            available: bytes.remaining(),
+        });
+    }
+    Ok(size)
+}
+
 impl Write for DirectoryEntry {
     fn write(&self, buf: &mut BytesMut) -> Result<(), ImprintError> {
         buf.put_u32_le(self.id);
@@ -335,11 +989,13 @@ impl Read for DirectoryEntry {
         let type_code = TypeCode::try_from(bytes.get_u8())?;
         let offset = bytes.get_u32_le();
 
+        // the length is only written alongside the entry when it can't be derived
         Ok((
             Self {
                 id,
                 type_code,
                 offset,
+                length: 0,
             },
             9,
         ))
@@ -423,40 +1079,238 @@ impl Read for Header {
     }
 }
 
+/// Write directory entries in the compact encoding: the id as a varint delta from
+/// the previous id, the type code, the offset as a zigzag varint delta from the
+/// previous offset (offsets aren't monotonic after a merge) and optionally the length
+fn write_compact_directory(directory: &[DirectoryEntry], with_lengths: bool, buf: &mut BytesMut) {
+    let mut prev_id = 0;
+    let mut prev_offset = 0u32;
+    for entry in directory {
+        varint::encode(entry.id - prev_id, buf);
+        buf.put_u8(entry.type_code as u8);
+        let delta = entry.offset.wrapping_sub(prev_offset) as i32;
+        varint::encode(varint::zigzag_encode(delta), buf);
+        if with_lengths {
+            varint::encode(entry.length, buf);
+        }
+        prev_id = entry.id;
+        prev_offset = entry.offset;
+    }
+}
+
+/// Read `count` directory entries in the compact encoding, returning the entries
+/// and number of bytes read
+fn read_compact_directory(
+    count: u32,
+    with_lengths: bool,
+    mut bytes: Bytes,
+) -> Result<(Vec<DirectoryEntry>, usize), ImprintError> {
+    let mut directory = Vec::with_capacity(count as usize);
+    let mut bytes_read = 0;
+    let mut prev_id = 0u32;
+    let mut prev_offset = 0u32;
+
+    for _ in 0..count {
+        let (id_delta, id_size) = varint::decode(bytes.clone())?;
+        bytes.advance(id_size);
+
+        if !bytes.has_remaining() {
+            return Err(ImprintError::BufferUnderflow {
+                needed: 1,
+                available: 0,
+            });
+        }
+        let type_code = TypeCode::try_from(bytes.get_u8())?;
+
+        let (offset_delta, offset_size) = varint::decode(bytes.clone())?;
+        bytes.advance(offset_size);
+        bytes_read += id_size + 1 + offset_size;
+
+        let mut length = 0;
+        if with_lengths {
+            let (len, len_size) = varint::decode(bytes.clone())?;
+            bytes.advance(len_size);
+            bytes_read += len_size;
+            length = len;
+        }
+
+        let id = prev_id
+            .checked_add(id_delta)
+            .ok_or_else(|| ImprintError::SchemaError("directory field id overflow".into()))?;
+        let offset = prev_offset.wrapping_add(varint::zigzag_decode(offset_delta) as u32);
+        directory.push(DirectoryEntry {
+            id,
+            type_code,
+            offset,
+            length,
+        });
+        prev_id = id;
+        prev_offset = offset;
+    }
+
+    Ok((directory, bytes_read))
+}
+
+/// Whether every field's length can be derived from the offset of the field that
+/// follows it in the payload, which holds unless the payload contains bytes that
+/// the directory doesn't reference (e.g. zombie data left behind by a merge)
+fn lengths_derivable(directory: &[DirectoryEntry], payload_len: usize) -> bool {
+    let contiguous = |entries: &mut dyn Iterator<Item = &DirectoryEntry>| {
+        let mut expected = 0;
+        for entry in entries {
+            if entry.offset as usize != expected {
+                return false;
+            }
+            expected += entry.length as usize;
+        }
+        expected == payload_len
+    };
+
+    if directory.is_sorted_by_key(|e| e.offset) {
+        contiguous(&mut directory.iter())
+    } else {
+        // merged directories are sorted by id, so walk the entries in payload order
+        let mut by_offset: Vec<&DirectoryEntry> = directory.iter().collect();
+        by_offset.sort_by_key(|e| e.offset);
+        contiguous(&mut by_offset.into_iter())
+    }
+}
+
+/// Derive the length of each field from the offset of the field that follows it in
+/// the payload, for directories written without lengths
+fn derive_lengths(
+    directory: &mut [DirectoryEntry],
+    payload_len: usize,
+) -> Result<(), ImprintError> {
+    let mut order: Vec<usize> = (0..directory.len()).collect();
+    if !directory.is_sorted_by_key(|e| e.offset) {
+        order.sort_by_key(|&idx| directory[idx].offset);
+    }
+
+    for (pos, &idx) in order.iter().enumerate() {
+        let end = order
+            .get(pos + 1)
+            .map(|&next| directory[next].offset as usize)
+            .unwrap_or(payload_len);
+        let offset = directory[idx].offset as usize;
+        if end < offset {
+            return Err(ImprintError::BufferUnderflow {
+                needed: offset,
+                available: payload_len,
+            });
+        }
+        directory[idx].length = (end - offset) as u32;
+    }
+    Ok(())
+}
+
+/// Check that the lengths written in a directory keep every field within the payload
+fn check_lengths(directory: &[DirectoryEntry], payload_len: usize) -> Result<(), ImprintError> {
+    for entry in directory {
+        let end = entry.offset as usize + entry.length as usize;
+        if end > payload_len {
+            return Err(ImprintError::BufferUnderflow {
+                needed: end,
+                available: payload_len,
+            });
+        }
+    }
+    Ok(())
+}
+
 impl Write for ImprintRecord {
     fn write(&self, buf: &mut BytesMut) -> Result<(), ImprintError> {
+        let start = buf.len();
         let header_size = HEADER_BYTES;
         let dir_count_size = DIR_COUNT_BYTES;
 
+        let with_lengths = self.header.flags.has_field_directory()
+            && !lengths_derivable(&self.directory, self.payload.len());
         let dir_entries_size = if self.header.flags.has_field_directory() {
-            self.directory.len() * DIR_ENTRY_BYTES
+            self.directory.len() * (DIR_ENTRY_BYTES + if with_lengths { 4 } else { 0 })
         } else {
             0
         };
 
-        let payload_size = self.payload.len();
-        buf.reserve(header_size + dir_count_size + dir_entries_size + payload_size);
+        // a compressed payload is framed by its codec and decompressed size, and the
+        // header records the size of the whole frame
+        let compression = self.payload.compression();
+        let mut frame = BytesMut::new();
+        let payload = if compression == Compression::None {
+            self.payload.get()?.clone()
+        } else {
+            frame.put_u8(compression as u8);
+            varint::encode_len(self.payload.len(), &mut frame)?;
+            self.payload.get_compressed()?
+        };
 
-        self.header.write(buf)?;
+        let payload_size = frame.len() + payload.len();
+        buf.reserve(
+            header_size + dir_count_size + dir_entries_size + payload_size + CHECKSUM_BYTES,
+        );
+
+        let header = Header {
+            flags: self
+                .header
+                .flags
+                .with(Flags::COMPRESSED, compression != Compression::None)
+                .with(Flags::FIELD_LENGTHS, with_lengths),
+            schema_id: self.header.schema_id,
+            payload_size: varint::checked_u32(payload_size, "payload size")?,
+        };
+        header.write(buf)?;
 
         if self.header.flags.has_field_directory() {
-            varint::encode(self.directory.len() as u32, buf);
-            for entry in &self.directory {
-                entry.write(buf)?;
+            varint::encode_len(self.directory.len(), buf)?;
+            if self.header.flags.has_compact_directory() {
+                write_compact_directory(&self.directory, with_lengths, buf);
+            } else {
+                for entry in &self.directory {
+                    entry.write(buf)?;
+                    if with_lengths {
+                        buf.put_u32_le(entry.length);
+                    }
+                }
             }
         }
 
-        buf.put_slice(&self.payload);
+        buf.put_slice(&frame);
+        buf.put_slice(&payload);
+
+        if self.header.flags.has_checksum() {
+            let checksum = crc32c::crc32c(&buf[start..]);
+            buf.put_u32_le(checksum);
+        }
 
         Ok(())
     }
 }
 
 impl Read for ImprintRecord {
-    fn read(mut bytes: Bytes) -> Result<(Self, usize), ImprintError> {
+    /// Records written without a field directory can't be read this way, since
+    /// none of their fields could be found. Read them through
+    /// [`SchemaRegistry::read`](crate::SchemaRegistry::read) instead.
+    fn read(bytes: Bytes) -> Result<(Self, usize), ImprintError> {
+        let (record, size) = Self::read_as_written(bytes)?;
+        if !record.header.flags.has_field_directory() {
+            return Err(ImprintError::SchemaError(format!(
+                "record of fieldspace {} with hash {:#x} has no field directory, \
+                 read it through SchemaRegistry::read",
+                record.header.schema_id.fieldspace_id, record.header.schema_id.schema_hash
+            )));
+        }
+        Ok((record, size))
+    }
+}
+
+impl ImprintRecord {
+    /// Read a record as it was written, leaving the directory empty if the record
+    /// was written without one
+    pub(crate) fn read_as_written(mut bytes: Bytes) -> Result<(Self, usize), ImprintError> {
+        let record_bytes = bytes.clone();
         let mut bytes_read = 0;
 
-        let (header, header_size) = Header::read(bytes.clone())?;
+        let (mut header, header_size) = Header::read(bytes.clone())?;
         bytes.advance(header_size);
         bytes_read += header_size;
 
@@ -466,18 +1320,85 @@ impl Read for ImprintRecord {
             bytes.advance(count_size);
             bytes_read += count_size;
 
-            for _ in 0..count {
-                let (entry, entry_size) = DirectoryEntry::read(bytes.clone())?;
-                bytes.advance(entry_size);
-                bytes_read += entry_size;
-                directory.push(entry);
+            let with_lengths = header.flags.has_field_lengths();
+            if header.flags.has_compact_directory() {
+                let (entries, entries_size) =
+                    read_compact_directory(count, with_lengths, bytes.clone())?;
+                bytes.advance(entries_size);
+                bytes_read += entries_size;
+                directory = entries;
+            } else {
+                for _ in 0..count {
+                    let (mut entry, entry_size) = DirectoryEntry::read(bytes.clone())?;
+                    bytes.advance(entry_size);
+                    bytes_read += entry_size;
+                    if with_lengths {
+                        if bytes.remaining() < 4 {
+                            return Err(ImprintError::BufferUnderflow {
+                                needed: 4,
+                                available: bytes.remaining(),
+                            });
+                        }
+                        entry.length = bytes.get_u32_le();
+                        bytes_read += 4;
+                    }
+                    directory.push(entry);
+                }
             }
         }
 
-        let payload = bytes.slice(..header.payload_size as usize);
+        if bytes.remaining() < header.payload_size as usize {
+            return Err(ImprintError::BufferUnderflow {
+                needed: header.payload_size as usize,
+                available: bytes.remaining(),
+            });
+        }
+        let mut payload_bytes = bytes.slice(..header.payload_size as usize);
         bytes.advance(header.payload_size as usize);
         bytes_read += header.payload_size as usize;
 
+        if header.flags.has_checksum() {
+            if bytes.remaining() < CHECKSUM_BYTES {
+                return Err(ImprintError::BufferUnderflow {
+                    needed: CHECKSUM_BYTES,
+                    available: bytes.remaining(),
+                });
+            }
+            let expected = bytes.get_u32_le();
+            let actual = crc32c::crc32c(&record_bytes[..bytes_read]);
+            if expected != actual {
+                return Err(ImprintError::ChecksumMismatch { expected, actual });
+            }
+            bytes_read += CHECKSUM_BYTES;
+        }
+
+        let payload = if header.flags.has_compression() {
+            if !payload_bytes.has_remaining() {
+                return Err(ImprintError::BufferUnderflow {
+                    needed: 1,
+                    available: 0,
+                });
+            }
+            let compression = Compression::try_from(payload_bytes.get_u8())?;
+            let (size, size_len) = varint::decode(payload_bytes.clone())?;
+            payload_bytes.advance(size_len);
+
+            // in memory the header describes the decompressed payload
+            header.payload_size = size;
+            Payload::compressed(payload_bytes, size as usize, compression)
+        } else {
+            Payload::new(payload_bytes, Compression::None)
+        };
+
+        // lengths are always known in memory, so whether they were written is decided
+        // again on every write
+        if header.flags.has_field_lengths() {
+            check_lengths(&directory, header.payload_size as usize)?;
+            header.flags = header.flags.with(Flags::FIELD_LENGTHS, false);
+        } else {
+            derive_lengths(&mut directory, header.payload_size as usize)?;
+        }
+
         Ok((
             Self {
                 header,
@@ -492,7 +1413,8 @@ impl Read for ImprintRecord {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::writer::ImprintWriter;
+    use crate::temporal::TimeUnit;
+    use crate::writer::{ImprintWriter, IntegerEncoding};
     use proptest::prelude::*;
     use proptest::strategy::{BoxedStrategy, Strategy};
     use proptest::test_runner::TestRunner;
@@ -507,11 +1429,67 @@ mod tests {
             any::<f32>().prop_map(Value::Float32),
             any::<f64>().prop_map(Value::Float64),
             prop::collection::vec(any::<u8>(), 0..100).prop_map(Value::Bytes),
-            ".*".prop_map(Value::String)
+            ".*".prop_map(Value::String),
+            arb_timestamp(),
+            any::<i32>().prop_map(Value::Date),
+            arb_time(),
+            arb_decimal(),
+            any::<[u8; 16]>().prop_map(Value::Uuid),
+            prop::collection::vec(any::<u8>(), 0..32).prop_map(Value::FixedBytes),
+            any::<u32>().prop_map(Value::Enum),
+            any::<i8>().prop_map(Value::Int8),
+            any::<i16>().prop_map(Value::Int16),
+            any::<u8>().prop_map(Value::UInt8),
+            any::<u16>().prop_map(Value::UInt16),
+            any::<u32>().prop_map(Value::UInt32),
+            any::<u64>().prop_map(Value::UInt64),
         ]
         .boxed()
     }
 
+    /// Fixed-size bytes that all share one size, as required within an array or map
+    fn arb_fixed_bytes_of_one_size() -> BoxedStrategy<Vec<Value>> {
+        (0..32usize)
+            .prop_flat_map(|size| {
+                prop::collection::vec(
+                    prop::collection::vec(any::<u8>(), size).prop_map(Value::FixedBytes),
+                    1..100,
+                )
+            })
+            .boxed()
+    }
+
+    fn arb_decimal() -> BoxedStrategy<Value> {
+        (1..=Decimal::MAX_PRECISION)
+            .prop_flat_map(|precision| {
+                let bound = 10i128.pow(precision as u32);
+                (-bound + 1..bound, Just(precision), 0..=precision)
+            })
+            .prop_map(|(unscaled, precision, scale)| {
+                Value::Decimal(Decimal::new(unscaled, precision, scale).unwrap())
+            })
+            .boxed()
+    }
+
+    fn arb_timestamp() -> BoxedStrategy<Value> {
+        (
+            any::<i64>(),
+            prop_oneof![
+                Just(TimeUnit::Seconds),
+                Just(TimeUnit::Millis),
+                Just(TimeUnit::Micros),
+                Just(TimeUnit::Nanos)
+            ],
+            any::<bool>(),
+        )
+            .prop_map(|(value, unit, utc)| Value::Timestamp(Timestamp::new(value, unit, utc)))
+            .boxed()
+    }
+
+    fn arb_time() -> BoxedStrategy<Value> {
+        (0..86_400_000_000_000i64).prop_map(Value::Time).boxed()
+    }
+
     // Helper function to generate homogeneous arrays of a specific type
     fn arb_homogeneous_array(element_gen: BoxedStrategy<Value>) -> BoxedStrategy<Value> {
         prop::collection::vec(element_gen, 0..100)
@@ -543,9 +1521,16 @@ mod tests {
             .boxed()
     }
 
+    fn arb_union() -> BoxedStrategy<Value> {
+        arb_primitive_value()
+            .prop_map(|value| Value::Union(Box::new(value)))
+            .boxed()
+    }
+
     fn arb_value() -> BoxedStrategy<Value> {
         prop_oneof![
             arb_primitive_value(),
+            arb_union(),
             arb_homogeneous_array(any::<i32>().prop_map(Value::Int32).boxed()),
             arb_simple_row()
         ]
@@ -653,6 +1638,77 @@ mod tests {
     }
 
     proptest! {
+        #[test]
+        fn prop_roundtrip_nullable_arrays(
+            values in prop::collection::vec(prop::option::of(".*"), 1..100)
+        ) {
+            // Given an array of optional strings
+            let array = Value::Array(
+                values.into_iter().map(|v| v.map_or(Value::Null, Value::String)).collect()
+            );
+
+            // When writing and reading it back
+            let mut buf = BytesMut::new();
+            array.write(&mut buf).map_err(|e| TestCaseError::fail(e.to_string()))?;
+            let bytes = buf.freeze();
+            let (read, size) = Value::read(TypeCode::Array, bytes.clone()).map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+            // Then it should be unchanged and sized without deserializing
+            prop_assert_eq!(read, array);
+            prop_assert_eq!(size, bytes.len());
+            prop_assert_eq!(value_size(TypeCode::Array, bytes).map_err(|e| TestCaseError::fail(e.to_string()))?, size);
+        }
+
+        #[test]
+        fn prop_roundtrip_mixed_arrays(
+            values in prop::collection::vec(prop_oneof![arb_primitive_value(), arb_simple_row()], 1..50)
+        ) {
+            // Given an array of elements of any type
+            let array = Value::Array(values);
+
+            // When writing and reading it back
+            let mut buf = BytesMut::new();
+            array.write(&mut buf).map_err(|e| TestCaseError::fail(e.to_string()))?;
+            let bytes = buf.freeze();
+            let (read, size) = Value::read(TypeCode::Array, bytes.clone()).map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+            // Then it should be unchanged and sized without deserializing
+            prop_assert_eq!(read, array);
+            prop_assert_eq!(size, bytes.len());
+            prop_assert_eq!(value_size(TypeCode::Array, bytes).map_err(|e| TestCaseError::fail(e.to_string()))?, size);
+        }
+
+        #[test]
+        fn prop_roundtrip_varint_integers(
+            int32 in any::<i32>(),
+            int64 in any::<i64>(),
+            uint32 in any::<u32>(),
+            uint64 in any::<u64>(),
+        ) {
+            // Given integers written as varints
+            let mut writer = ImprintWriter::new(SchemaId {
+                fieldspace_id: 1,
+                schema_hash: 0xdeadbeef,
+            }).map_err(|e| TestCaseError::fail(e.to_string()))?;
+            writer.set_integer_encoding(IntegerEncoding::Varint);
+            let values: [Value; 4] = [int32.into(), int64.into(), uint32.into(), uint64.into()];
+            for (id, value) in values.iter().enumerate() {
+                writer.add_field(id as u32, value.clone()).map_err(|e| TestCaseError::fail(e.to_string()))?;
+            }
+
+            // When roundtripping the record
+            let record = writer.build().map_err(|e| TestCaseError::fail(e.to_string()))?;
+            let mut buf = BytesMut::new();
+            record.write(&mut buf).map_err(|e| TestCaseError::fail(e.to_string()))?;
+            let (read, _) = ImprintRecord::read(buf.freeze()).map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+            // Then every value should read back as its logical type
+            for (id, value) in values.into_iter().enumerate() {
+                let got = read.get_value(id as u32).map_err(|e| TestCaseError::fail(e.to_string()))?;
+                prop_assert_eq!(got, Some(value));
+            }
+        }
+
         #[test]
         fn test_roundtrip_simple_record(
             null in Just(Value::Null),
@@ -738,6 +1794,21 @@ mod tests {
                 Value::Array(_) => arb_homogeneous_array(prop::collection::vec(any::<i32>().prop_map(Value::Int32), 0..100).prop_map(Value::Array).boxed()),
                 Value::Map(_) => arb_homogeneous_array(prop::collection::hash_map(any::<i32>().prop_map(MapKey::Int32), any::<i32>().prop_map(Value::Int32), 0..100).prop_map(Value::Map).boxed()),
                 Value::Row(_) => arb_homogeneous_array(arb_simple_row().boxed()),
+                Value::Timestamp(_) => arb_homogeneous_array(arb_timestamp()),
+                Value::Date(_) => arb_homogeneous_array(any::<i32>().prop_map(Value::Date).boxed()),
+                Value::Time(_) => arb_homogeneous_array(arb_time()),
+                Value::Decimal(_) => arb_homogeneous_array(arb_decimal()),
+                Value::Uuid(_) => arb_homogeneous_array(any::<[u8; 16]>().prop_map(Value::Uuid).boxed()),
+                Value::FixedBytes(_) => arb_fixed_bytes_of_one_size().prop_map(Value::Array).boxed(),
+                Value::Enum(_) => arb_homogeneous_array(any::<u32>().prop_map(Value::Enum).boxed()),
+                Value::Int8(_) => arb_homogeneous_array(any::<i8>().prop_map(Value::Int8).boxed()),
+                Value::Int16(_) => arb_homogeneous_array(any::<i16>().prop_map(Value::Int16).boxed()),
+                Value::UInt8(_) => arb_homogeneous_array(any::<u8>().prop_map(Value::UInt8).boxed()),
+                Value::UInt16(_) => arb_homogeneous_array(any::<u16>().prop_map(Value::UInt16).boxed()),
+                Value::UInt32(_) => arb_homogeneous_array(any::<u32>().prop_map(Value::UInt32).boxed()),
+                Value::UInt64(_) => arb_homogeneous_array(any::<u64>().prop_map(Value::UInt64).boxed()),
+                Value::Union(_) => arb_homogeneous_array(arb_union()),
+                Value::Tombstone => unreachable!("tombstones are not generated"),
             };
 
             // When generating an array
@@ -770,7 +1841,11 @@ mod tests {
                 Just(TypeCode::Int32),
                 Just(TypeCode::Int64),
                 Just(TypeCode::Bytes),
-                Just(TypeCode::String)
+                Just(TypeCode::String),
+                Just(TypeCode::Uuid),
+                Just(TypeCode::Int8),
+                Just(TypeCode::UInt64),
+                Just(TypeCode::Bool)
             ],
             base_value in arb_value()
         ) {
@@ -780,6 +1855,10 @@ mod tests {
                 TypeCode::Int64 => any::<i64>().prop_map(MapKey::Int64).boxed(),
                 TypeCode::Bytes => prop::collection::vec(any::<u8>(), 0..100).prop_map(MapKey::Bytes).boxed(),
                 TypeCode::String => ".*".prop_map(MapKey::String).boxed(),
+                TypeCode::Uuid => any::<[u8; 16]>().prop_map(MapKey::Uuid).boxed(),
+                TypeCode::Int8 => any::<i8>().prop_map(MapKey::Int8).boxed(),
+                TypeCode::UInt64 => any::<u64>().prop_map(MapKey::UInt64).boxed(),
+                TypeCode::Bool => any::<bool>().prop_map(MapKey::Bool).boxed(),
                 _ => panic!("Unsupported key type"),
             };
 
@@ -796,6 +1875,21 @@ mod tests {
                 Value::Array(_) => arb_homogeneous_array(prop::collection::vec(any::<i32>().prop_map(Value::Int32), 0..100).prop_map(Value::Array).boxed()),
                 Value::Map(_) => arb_homogeneous_array(prop::collection::hash_map(any::<i32>().prop_map(MapKey::Int32), any::<i32>().prop_map(Value::Int32), 0..100).prop_map(Value::Map).boxed()),
                 Value::Row(_) => arb_homogeneous_array(arb_simple_row().boxed()),
+                Value::Timestamp(_) => arb_timestamp(),
+                Value::Date(_) => any::<i32>().prop_map(Value::Date).boxed(),
+                Value::Time(_) => arb_time(),
+                Value::Decimal(_) => arb_decimal(),
+                Value::Uuid(_) => any::<[u8; 16]>().prop_map(Value::Uuid).boxed(),
+                Value::FixedBytes(v) => prop::collection::vec(any::<u8>(), v.len()).prop_map(Value::FixedBytes).boxed(),
+                Value::Enum(_) => any::<u32>().prop_map(Value::Enum).boxed(),
+                Value::Int8(_) => any::<i8>().prop_map(Value::Int8).boxed(),
+                Value::Int16(_) => any::<i16>().prop_map(Value::Int16).boxed(),
+                Value::UInt8(_) => any::<u8>().prop_map(Value::UInt8).boxed(),
+                Value::UInt16(_) => any::<u16>().prop_map(Value::UInt16).boxed(),
+                Value::UInt32(_) => any::<u32>().prop_map(Value::UInt32).boxed(),
+                Value::UInt64(_) => any::<u64>().prop_map(Value::UInt64).boxed(),
+                Value::Union(_) => arb_union(),
+                Value::Tombstone => unreachable!("tombstones are not generated"),
             };
 
             // Create a strategy for maps with these key and value types
@@ -826,6 +1920,390 @@ mod tests {
         }
     }
 
+    #[cfg(any(feature = "lz4", feature = "zstd"))]
+    fn roundtrip_compressed_record(compression: Compression) {
+        // Given a record with a large compressible field that should be compressed
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xdeadbeef,
+        })
+        .unwrap();
+        writer.set_compression(compression);
+        writer.add_field(1, 42.into()).unwrap();
+        writer
+            .add_field(2, "a long description ".repeat(50).into())
+            .unwrap();
+        let record = writer.build().unwrap();
+
+        // When we serialize it
+        let mut buf = BytesMut::new();
+        record.write(&mut buf).unwrap();
+
+        // Then the compressed flag should be set and the record smaller than its payload
+        assert_eq!(buf[2] & Flags::COMPRESSED, Flags::COMPRESSED);
+        assert!(buf.len() < record.payload.len());
+
+        // And when we deserialize it, nothing should be decompressed until first access
+        let (read, size) = ImprintRecord::read(buf.clone().freeze()).unwrap();
+        assert_eq!(size, buf.len());
+        assert_eq!(read.compression(), compression);
+        assert!(read.payload.decompressed.get().is_none());
+
+        // And the values should match
+        assert_eq!(read.get_value(1).unwrap(), Some(42.into()));
+        assert_eq!(
+            read.get_value(2).unwrap(),
+            Some("a long description ".repeat(50).into())
+        );
+        assert_eq!(read, record);
+
+        // And re-serializing should reuse the compressed bytes
+        let mut rewritten = BytesMut::new();
+        read.write(&mut rewritten).unwrap();
+        assert_eq!(rewritten, buf);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_roundtrip_lz4_compressed_record() {
+        roundtrip_compressed_record(Compression::Lz4);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_roundtrip_zstd_compressed_record() {
+        roundtrip_compressed_record(Compression::Zstd);
+    }
+
+    #[test]
+    fn test_unsupported_compression_codec() {
+        // Given a record whose compressed payload names an unknown codec
+        let mut buf = BytesMut::new();
+        Header {
+            flags: Flags::new(Flags::COMPRESSED),
+            schema_id: SchemaId {
+                fieldspace_id: 1,
+                schema_hash: 0xdeadbeef,
+            },
+            payload_size: 2,
+        }
+        .write(&mut buf)
+        .unwrap();
+        buf.put_u8(0x7f);
+        buf.put_u8(0x00);
+
+        // When trying to read
+        // Then it should return an unsupported compression error
+        assert!(matches!(
+            ImprintRecord::read(buf.freeze()),
+            Err(ImprintError::UnsupportedCompression(0x7f))
+        ));
+    }
+
+    fn create_checksummed_record() -> ImprintRecord {
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xdeadbeef,
+        })
+        .unwrap();
+        writer.set_checksum(true);
+        writer.add_field(1, 42.into()).unwrap();
+        writer.add_field(2, "checked".into()).unwrap();
+        writer.build().unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip_checksummed_record() {
+        // Given a record with a checksum
+        let record = create_checksummed_record();
+
+        // When we serialize and deserialize it
+        let mut buf = BytesMut::new();
+        record.write(&mut buf).unwrap();
+        let (read, size) = ImprintRecord::read(buf.clone().freeze()).unwrap();
+
+        // Then the checksum should be verified and consumed
+        assert_eq!(size, buf.len());
+        assert!(read.has_checksum());
+        assert_eq!(read.get_value(2).unwrap(), Some("checked".into()));
+    }
+
+    #[test]
+    fn test_checksum_detects_corruption() {
+        // Given a serialized record with a checksum
+        let record = create_checksummed_record();
+        let mut buf = BytesMut::new();
+        record.write(&mut buf).unwrap();
+
+        // When a single bit is flipped anywhere in the record
+        for idx in 0..buf.len() {
+            let mut corrupted = buf.clone();
+            corrupted[idx] ^= 0x10;
+
+            // Then reading should fail rather than return garbage
+            assert!(
+                ImprintRecord::read(corrupted.freeze()).is_err(),
+                "corruption at byte {} went undetected",
+                idx
+            );
+        }
+
+        // And a corrupted payload should be reported as a checksum mismatch
+        let mut corrupted = buf.clone();
+        let last = corrupted.len() - CHECKSUM_BYTES - 1;
+        corrupted[last] ^= 0x01;
+        assert!(matches!(
+            ImprintRecord::read(corrupted.freeze()),
+            Err(ImprintError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_roundtrip_compact_directory() {
+        // Given the same record with a regular and a compact directory
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xdeadbeef,
+        })
+        .unwrap();
+        writer.add_field(1, 42.into()).unwrap();
+        writer.add_field(2, "hello".into()).unwrap();
+        writer.add_field(300, true.into()).unwrap();
+        let record = writer.build().unwrap();
+
+        let mut compact = record.clone();
+        compact.set_compact_directory(true);
+
+        // When serializing both
+        let mut buf = BytesMut::new();
+        record.write(&mut buf).unwrap();
+        let mut compact_buf = BytesMut::new();
+        compact.write(&mut compact_buf).unwrap();
+
+        // Then the compact record should be smaller
+        assert!(compact_buf.len() < buf.len());
+
+        // And when deserializing it the directory and values should be preserved
+        let (read, size) = ImprintRecord::read(compact_buf.clone().freeze()).unwrap();
+        assert_eq!(size, compact_buf.len());
+        assert_eq!(read.directory, record.directory);
+        assert_eq!(read.get_value(2).unwrap(), Some("hello".into()));
+        assert_eq!(read.get_value(300).unwrap(), Some(true.into()));
+    }
+
+    #[test]
+    fn test_compact_directory_with_non_monotonic_offsets() {
+        // Given a directory whose offsets go backwards, as after a merge
+        let directory = vec![
+            DirectoryEntry {
+                id: 1,
+                type_code: TypeCode::Int32,
+                offset: 100,
+                length: 4,
+            },
+            DirectoryEntry {
+                id: 2,
+                type_code: TypeCode::Int64,
+                offset: 0,
+                length: 8,
+            },
+            DirectoryEntry {
+                id: u32::MAX,
+                type_code: TypeCode::Bool,
+                offset: u32::MAX,
+                length: 1,
+            },
+        ];
+
+        // When writing and reading it in the compact encoding
+        let mut buf = BytesMut::new();
+        write_compact_directory(&directory, true, &mut buf);
+        let (read, size) = read_compact_directory(3, true, buf.clone().freeze()).unwrap();
+
+        // Then it should be preserved
+        assert_eq!(size, buf.len());
+        assert_eq!(read, directory);
+    }
+
+    #[test]
+    fn test_reject_out_of_range_time_of_day() {
+        // Given a time of day past midnight
+        let mut buf = BytesMut::new();
+        buf.put_i64_le(86_400_000_000_000);
+
+        // When writing or reading it
+        // Then it should return a schema error
+        assert!(matches!(
+            Value::Time(86_400_000_000_000).write(&mut BytesMut::new()),
+            Err(ImprintError::SchemaError(_))
+        ));
+        assert!(matches!(
+            Value::read(TypeCode::Time, buf.freeze()),
+            Err(ImprintError::SchemaError(_))
+        ));
+    }
+
+    #[test]
+    fn test_reject_decimal_exceeding_precision() {
+        // Given a decimal whose unscaled value has more digits than its precision
+        let mut buf = BytesMut::new();
+        buf.put_u8(2);
+        buf.put_u8(0);
+        buf.put_i128_le(100);
+
+        // When reading it
+        // Then it should return a schema error
+        assert!(matches!(
+            Value::read(TypeCode::Decimal, buf.freeze()),
+            Err(ImprintError::SchemaError(_))
+        ));
+    }
+
+    #[test]
+    fn test_fixed_bytes_size_written_once() {
+        // Given an array of three 4-byte fixed-size values
+        let array = Value::Array(vec![Value::FixedBytes(vec![1, 2, 3, 4]); 3]);
+
+        // When writing it
+        let mut buf = BytesMut::new();
+        array.write(&mut buf).unwrap();
+
+        // Then the size should follow the element type code once, with no per-element prefix
+        assert_eq!(buf.len(), 1 + 1 + 1 + 3 * 4);
+        assert_eq!(buf[2], 4);
+
+        // And it should read back
+        let (read, size) = Value::read(TypeCode::Array, buf.freeze()).unwrap();
+        assert_eq!(size, 15);
+        assert_eq!(read, array);
+    }
+
+    #[test]
+    fn test_nullable_array_layout() {
+        // Given an array of int32s with nulls in between
+        let array = Value::Array(vec![
+            Value::Int32(1),
+            Value::Null,
+            Value::Null,
+            Value::Int32(2),
+            Value::Null,
+            Value::Null,
+            Value::Null,
+            Value::Null,
+            Value::Int32(3),
+        ]);
+
+        // When writing it
+        let mut buf = BytesMut::new();
+        array.write(&mut buf).unwrap();
+
+        // Then it should write a validity bitmap and only the present values
+        assert_eq!(buf[0], 9);
+        assert_eq!(buf[1], TypeCode::Int32 as u8 | NULLABLE_ELEMENTS);
+        assert_eq!(&buf[2..4], &[0b0000_1001, 0b0000_0001]);
+        assert_eq!(buf.len(), 4 + 3 * 4);
+        assert_eq!(Value::read(TypeCode::Array, buf.freeze()).unwrap().0, array);
+    }
+
+    #[test]
+    fn test_equal_maps_encode_to_equal_bytes() {
+        // Given equal maps built in different insertion orders, each with its own hash seed
+        let keys: Vec<u32> = (0..64).collect();
+        let forward: HashMap<MapKey, Value> = keys
+            .iter()
+            .map(|&k| (k.into(), (k as i64).into()))
+            .collect();
+        let backward: HashMap<MapKey, Value> = keys
+            .iter()
+            .rev()
+            .map(|&k| (k.into(), (k as i64).into()))
+            .collect();
+
+        // When writing both
+        let mut forward_buf = BytesMut::new();
+        Value::Map(forward).write(&mut forward_buf).unwrap();
+        let mut backward_buf = BytesMut::new();
+        Value::Map(backward).write(&mut backward_buf).unwrap();
+
+        // Then the bytes should be identical, with keys in ascending order
+        assert_eq!(forward_buf, backward_buf);
+        assert_eq!(&forward_buf[3..7], &0u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_reject_fixed_bytes_of_different_sizes() {
+        // Given an array of fixed-size bytes that don't share a size
+        let array = Value::Array(vec![
+            Value::FixedBytes(vec![1, 2]),
+            Value::FixedBytes(vec![1, 2, 3]),
+        ]);
+
+        // When writing it
+        // Then it should return a schema error
+        assert!(matches!(
+            array.write(&mut BytesMut::new()),
+            Err(ImprintError::SchemaError(_))
+        ));
+    }
+
+    #[test]
+    fn test_fixed_size_fields_bounded_by_directory() {
+        // Given a record with a uuid, fixed-size bytes and an enum
+        let uuid = [0xab; 16];
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xdeadbeef,
+        })
+        .unwrap();
+        writer.add_field(1, Value::Uuid(uuid)).unwrap();
+        writer.add_field(2, Value::FixedBytes(vec![7; 5])).unwrap();
+        writer.add_field(3, Value::Enum(300)).unwrap();
+        let record = writer.build().unwrap();
+
+        // When roundtripping it
+        let mut buf = BytesMut::new();
+        record.write(&mut buf).unwrap();
+        let (read, _) = ImprintRecord::read(buf.freeze()).unwrap();
+
+        // Then each value should take only its own bytes
+        assert_eq!(read.payload.len(), 16 + 5 + 2);
+        assert_eq!(read.get_value(1).unwrap(), Some(Value::Uuid(uuid)));
+        assert_eq!(
+            read.get_value(2).unwrap(),
+            Some(Value::FixedBytes(vec![7; 5]))
+        );
+        assert_eq!(read.get_value(3).unwrap(), Some(Value::Enum(300)));
+    }
+
+    #[test]
+    fn test_get_value_as_wider_type() {
+        // Given a record with narrow integer fields
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0xdeadbeef,
+        })
+        .unwrap();
+        writer.add_field(1, 7i32.into()).unwrap();
+        writer.add_field(2, 3u16.into()).unwrap();
+        let record = writer.build().unwrap();
+
+        // When reading them as the wider types a reader expects
+        // Then they should be widened, and narrowing should be rejected
+        assert_eq!(
+            record.get_value_as(1, TypeCode::Int64).unwrap(),
+            Some(Value::Int64(7))
+        );
+        assert_eq!(
+            record.get_value_as(2, TypeCode::UInt64).unwrap(),
+            Some(Value::UInt64(3))
+        );
+        assert_eq!(record.get_value_as(3, TypeCode::Int64).unwrap(), None);
+        assert!(matches!(
+            record.get_value_as(1, TypeCode::Int16),
+            Err(ImprintError::TypeMismatch { .. })
+        ));
+    }
+
     #[test]
     fn test_duplicate_field_id() {
         let mut writer = ImprintWriter::new(SchemaId {
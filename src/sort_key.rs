@@ -0,0 +1,477 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::{
+    canonical::{canonical_f32, canonical_f64},
+    decimal::Decimal,
+    error::ImprintError,
+    temporal::Timestamp,
+    types::{ImprintRecord, TypeCode, Value},
+};
+
+/// Direction a field sorts in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Where null and missing fields sort relative to present ones, in either direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NullOrder {
+    #[default]
+    NullsFirst,
+    NullsLast,
+}
+
+/// Markers written ahead of each field, which are never inverted for descending fields
+const NULL_FIRST: u8 = 0x00;
+const PRESENT: u8 = 0x01;
+const NULL_LAST: u8 = 0x02;
+
+/// Escape for a zero byte inside bytes and strings, and the terminator that ends them
+const ESCAPE: u8 = 0xff;
+const TERMINATOR: u8 = 0x01;
+
+impl ImprintRecord {
+    /// Encode the given fields into a key whose byte-wise (`memcmp`) order is the
+    /// order of their values, for shuffles, range partitioning and state-store keys.
+    ///
+    /// Fields compare in the order they're listed, each in its own direction, and
+    /// missing or deleted fields sort as nulls. Integers compare by value however they were
+    /// written, floats compare with `-0.0` equal to `0.0` and all NaNs equal and
+    /// after infinity, decimals compare numerically and timestamps by instant.
+    /// Arrays, maps and rows have no order and are rejected.
+    ///
+    /// Keys decode with [`decode_sort_key`] given the type of each field.
+    pub fn sort_key(&self, fields: &[(u32, SortOrder, NullOrder)]) -> Result<Bytes, ImprintError> {
+        let mut buf = BytesMut::new();
+        for &(field_id, order, nulls) in fields {
+            match self.get_value(field_id)? {
+                None | Some(Value::Null | Value::Tombstone) => buf.put_u8(null_marker(nulls)),
+                Some(value) => {
+                    buf.put_u8(PRESENT);
+                    let start = buf.len();
+                    write_key(&value, &mut buf)?;
+                    if order == SortOrder::Desc {
+                        buf[start..].iter_mut().for_each(|b| *b = !*b);
+                    }
+                }
+            }
+        }
+        Ok(buf.freeze())
+    }
+}
+
+/// Decode a key written by [`ImprintRecord::sort_key`] given the type, order and
+/// null order of each of its fields.
+///
+/// Null and missing fields decode as [`Value::Null`], varint types as their
+/// fixed-width types, and decimals at their smallest scale with the maximum
+/// precision.
+pub fn decode_sort_key(
+    key: &[u8],
+    fields: &[(TypeCode, SortOrder, NullOrder)],
+) -> Result<Vec<Value>, ImprintError> {
+    let mut reader = KeyReader {
+        bytes: key,
+        mask: 0,
+    };
+    let mut values = Vec::with_capacity(fields.len());
+    for &(type_code, order, nulls) in fields {
+        reader.mask = 0;
+        let marker = reader.u8()?;
+        if marker == null_marker(nulls) {
+            values.push(Value::Null);
+            continue;
+        }
+        if marker != PRESENT {
+            return Err(ImprintError::InvalidSortKey(format!(
+                "unexpected field marker {:#x}",
+                marker
+            )));
+        }
+        reader.mask = if order == SortOrder::Desc { 0xff } else { 0 };
+        values.push(read_key(type_code, &mut reader)?);
+    }
+    if !reader.bytes.is_empty() {
+        return Err(ImprintError::InvalidSortKey(format!(
+            "{} trailing bytes",
+            reader.bytes.len()
+        )));
+    }
+    Ok(values)
+}
+
+fn null_marker(nulls: NullOrder) -> u8 {
+    match nulls {
+        NullOrder::NullsFirst => NULL_FIRST,
+        NullOrder::NullsLast => NULL_LAST,
+    }
+}
+
+fn unsortable(type_code: TypeCode) -> ImprintError {
+    ImprintError::InvalidSortKey(format!("{:?} values have no order", type_code))
+}
+
+/// Write a value so that unsigned byte-wise comparison matches value order
+fn write_key(value: &Value, buf: &mut BytesMut) -> Result<(), ImprintError> {
+    match value {
+        Value::Null | Value::Tombstone => {}
+        Value::Bool(v) => buf.put_u8(*v as u8),
+        // signed integers are big-endian with the sign bit flipped
+        Value::Int8(v) => buf.put_u8(*v as u8 ^ 1 << 7),
+        Value::Int16(v) => buf.put_u16(*v as u16 ^ 1 << 15),
+        Value::Int32(v) | Value::Date(v) => buf.put_u32(*v as u32 ^ 1 << 31),
+        Value::Int64(v) | Value::Time(v) => buf.put_u64(*v as u64 ^ 1 << 63),
+        Value::UInt8(v) => buf.put_u8(*v),
+        Value::UInt16(v) => buf.put_u16(*v),
+        Value::UInt32(v) | Value::Enum(v) => buf.put_u32(*v),
+        Value::UInt64(v) => buf.put_u64(*v),
+        // positive floats get their sign bit set and negative floats are inverted,
+        // so that larger magnitudes sort lower
+        Value::Float32(f) => {
+            let bits = canonical_f32(*f);
+            buf.put_u32(if bits >> 31 == 1 {
+                !bits
+            } else {
+                bits | 1 << 31
+            });
+        }
+        Value::Float64(f) => {
+            let bits = canonical_f64(*f);
+            buf.put_u64(if bits >> 63 == 1 {
+                !bits
+            } else {
+                bits | 1 << 63
+            });
+        }
+        Value::Bytes(v) | Value::FixedBytes(v) => write_escaped(v, buf),
+        Value::String(s) => write_escaped(s.as_bytes(), buf),
+        Value::Uuid(v) => buf.put_slice(v),
+        Value::Timestamp(t) => {
+            buf.put_u128(t.as_nanos() as u128 ^ 1 << 127);
+            buf.put_u8(t.metadata());
+        }
+        Value::Decimal(d) => {
+            // the integral part and the fraction at the maximum scale both fit in
+            // 128 bits, which avoids scaling the whole value up
+            let factor = 10i128.pow(d.scale() as u32);
+            let fraction = d.unscaled().rem_euclid(factor) as u128
+                * 10u128.pow((Decimal::MAX_PRECISION - d.scale()) as u32);
+            buf.put_u128(d.unscaled().div_euclid(factor) as u128 ^ 1 << 127);
+            buf.put_u128(fraction);
+        }
+        Value::Union(inner) => {
+            buf.put_u8(inner.type_code().logical() as u8);
+            write_key(inner, buf)?;
+        }
+        Value::Array(_) | Value::Map(_) | Value::Row(_) => {
+            return Err(unsortable(value.type_code()));
+        }
+    }
+    Ok(())
+}
+
+/// Write bytes with each zero byte escaped and a terminator that sorts below any
+/// other byte, so that a prefix sorts before the values it's a prefix of
+fn write_escaped(bytes: &[u8], buf: &mut BytesMut) {
+    for &byte in bytes {
+        buf.put_u8(byte);
+        if byte == 0 {
+            buf.put_u8(ESCAPE);
+        }
+    }
+    buf.put_slice(&[0, TERMINATOR]);
+}
+
+fn read_key(type_code: TypeCode, reader: &mut KeyReader) -> Result<Value, ImprintError> {
+    let value = match type_code.logical() {
+        TypeCode::Null => Value::Null,
+        TypeCode::Bool => Value::Bool(reader.u8()? != 0),
+        TypeCode::Int8 => Value::Int8((reader.u8()? ^ 1 << 7) as i8),
+        TypeCode::Int16 => Value::Int16((u16::from_be_bytes(reader.array()?) ^ 1 << 15) as i16),
+        TypeCode::Int32 => Value::Int32((u32::from_be_bytes(reader.array()?) ^ 1 << 31) as i32),
+        TypeCode::Date => Value::Date((u32::from_be_bytes(reader.array()?) ^ 1 << 31) as i32),
+        TypeCode::Int64 => Value::Int64((u64::from_be_bytes(reader.array()?) ^ 1 << 63) as i64),
+        TypeCode::Time => Value::Time((u64::from_be_bytes(reader.array()?) ^ 1 << 63) as i64),
+        TypeCode::UInt8 => Value::UInt8(reader.u8()?),
+        TypeCode::UInt16 => Value::UInt16(u16::from_be_bytes(reader.array()?)),
+        TypeCode::UInt32 => Value::UInt32(u32::from_be_bytes(reader.array()?)),
+        TypeCode::Enum => Value::Enum(u32::from_be_bytes(reader.array()?)),
+        TypeCode::UInt64 => Value::UInt64(u64::from_be_bytes(reader.array()?)),
+        TypeCode::Float32 => {
+            let bits = u32::from_be_bytes(reader.array()?);
+            Value::Float32(f32::from_bits(if bits >> 31 == 1 {
+                bits ^ 1 << 31
+            } else {
+                !bits
+            }))
+        }
+        TypeCode::Float64 => {
+            let bits = u64::from_be_bytes(reader.array()?);
+            Value::Float64(f64::from_bits(if bits >> 63 == 1 {
+                bits ^ 1 << 63
+            } else {
+                !bits
+            }))
+        }
+        TypeCode::Bytes => Value::Bytes(reader.escaped()?),
+        TypeCode::FixedBytes => Value::FixedBytes(reader.escaped()?),
+        TypeCode::String => Value::String(
+            String::from_utf8(reader.escaped()?).map_err(|_| ImprintError::InvalidUtf8String)?,
+        ),
+        TypeCode::Uuid => Value::Uuid(reader.array()?),
+        TypeCode::Timestamp => {
+            let nanos = (u128::from_be_bytes(reader.array()?) ^ 1 << 127) as i128;
+            let mut timestamp = Timestamp::from_metadata(reader.u8()?, 0)?;
+            timestamp.value = i64::try_from(nanos / timestamp.unit.nanos())
+                .map_err(|_| ImprintError::InvalidSortKey("timestamp out of range".into()))?;
+            Value::Timestamp(timestamp)
+        }
+        TypeCode::Decimal => {
+            let integral = (u128::from_be_bytes(reader.array()?) ^ 1 << 127) as i128;
+            let mut fraction = u128::from_be_bytes(reader.array()?);
+            let mut scale = Decimal::MAX_PRECISION;
+            while scale > 0 && fraction % 10 == 0 {
+                fraction /= 10;
+                scale -= 1;
+            }
+            let unscaled = integral
+                .checked_mul(10i128.pow(scale as u32))
+                .and_then(|v| v.checked_add(i128::try_from(fraction).ok()?))
+                .ok_or_else(|| ImprintError::InvalidSortKey("decimal out of range".into()))?;
+            Value::Decimal(Decimal::new(unscaled, Decimal::MAX_PRECISION, scale)?)
+        }
+        TypeCode::Union => {
+            let branch = TypeCode::try_from(reader.u8()?)?;
+            Value::Union(Box::new(read_key(branch, reader)?))
+        }
+        other => return Err(unsortable(other)),
+    };
+    Ok(value)
+}
+
+/// Reads a key, inverting the bytes of descending fields as they're read
+struct KeyReader<'a> {
+    bytes: &'a [u8],
+    mask: u8,
+}
+
+impl KeyReader<'_> {
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ImprintError> {
+        if self.bytes.len() < N {
+            return Err(ImprintError::BufferUnderflow {
+                needed: N,
+                available: self.bytes.len(),
+            });
+        }
+        let (head, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(std::array::from_fn(|i| head[i] ^ self.mask))
+    }
+
+    fn u8(&mut self) -> Result<u8, ImprintError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn escaped(&mut self) -> Result<Vec<u8>, ImprintError> {
+        let mut out = Vec::new();
+        loop {
+            match self.u8()? {
+                0 => match self.u8()? {
+                    ESCAPE => out.push(0),
+                    TERMINATOR => return Ok(out),
+                    byte => {
+                        return Err(ImprintError::InvalidSortKey(format!(
+                            "invalid escape {:#x}",
+                            byte
+                        )));
+                    }
+                },
+                byte => out.push(byte),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntegerEncoding, temporal::TimeUnit, writer::test_record};
+    use proptest::prelude::*;
+
+    #[test]
+    fn should_order_keys_like_values() {
+        // Given rows with a nullable int and a string, some written as varints
+        let rows = [
+            test_record(IntegerEncoding::Auto, vec![(1, 5.into()), (2, "b".into())]),
+            test_record(IntegerEncoding::Auto, vec![(2, "a".into())]),
+            test_record(
+                IntegerEncoding::Auto,
+                vec![(1, (-300).into()), (2, "a\0".into())],
+            ),
+            test_record(IntegerEncoding::Auto, vec![(1, 5.into()), (2, "ba".into())]),
+            test_record(
+                IntegerEncoding::Auto,
+                vec![(1, Value::Null), (2, "c".into())],
+            ),
+            test_record(
+                IntegerEncoding::Auto,
+                vec![(1, (-300).into()), (2, "a".into())],
+            ),
+        ];
+
+        // When sorting them by key on the int ascending with nulls last, then on
+        // the string descending
+        let fields = [
+            (1, SortOrder::Asc, NullOrder::NullsLast),
+            (2, SortOrder::Desc, NullOrder::NullsFirst),
+        ];
+        let mut keys: Vec<_> = rows
+            .iter()
+            .map(|row| row.sort_key(&fields).unwrap())
+            .collect();
+        keys.sort();
+
+        // Then they should be in value order
+        let types = [
+            (TypeCode::Int32, SortOrder::Asc, NullOrder::NullsLast),
+            (TypeCode::String, SortOrder::Desc, NullOrder::NullsFirst),
+        ];
+        let sorted: Vec<_> = keys
+            .iter()
+            .map(|key| decode_sort_key(key, &types).unwrap())
+            .collect();
+        let expected: Vec<Vec<Value>> = vec![
+            vec![(-300).into(), "a\0".into()],
+            vec![(-300).into(), "a".into()],
+            vec![5.into(), "ba".into()],
+            vec![5.into(), "b".into()],
+            vec![Value::Null, "c".into()],
+            vec![Value::Null, "a".into()],
+        ];
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn should_roundtrip_every_sortable_type() {
+        // Given a row with a field of every sortable type
+        let values = vec![
+            Value::Bool(true),
+            Value::Int8(-8),
+            Value::Int16(-16),
+            Value::Int32(i32::MIN),
+            Value::Int64(-64),
+            Value::UInt8(8),
+            Value::UInt16(16),
+            Value::UInt32(u32::MAX),
+            Value::UInt64(64),
+            Value::Float32(-1.5),
+            Value::Float64(f64::INFINITY),
+            Value::Bytes(vec![0, 1, 0, 0xff]),
+            Value::String("héllo".into()),
+            Value::Timestamp(Timestamp::new(-1_500, TimeUnit::Millis, true)),
+            Value::Date(-1),
+            Value::Time(1_000),
+            Value::Decimal(Decimal::new(-12_340, 10, 4).unwrap()),
+            Value::Uuid([7; 16]),
+            Value::FixedBytes(vec![0; 4]),
+            Value::Enum(3),
+            Value::Union(Box::new("branch".into())),
+        ];
+        let row = test_record(
+            IntegerEncoding::Auto,
+            values
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(i, v)| (i as u32, v))
+                .collect(),
+        );
+
+        for order in [SortOrder::Asc, SortOrder::Desc] {
+            // When encoding and decoding a key over all of them
+            let fields: Vec<_> = (0..values.len() as u32)
+                .map(|id| (id, order, NullOrder::NullsFirst))
+                .collect();
+            let types: Vec<_> = values
+                .iter()
+                .map(|v| (v.type_code(), order, NullOrder::NullsFirst))
+                .collect();
+            let key = row.sort_key(&fields).unwrap();
+
+            // Then the values should decode unchanged
+            assert_eq!(decode_sort_key(&key, &types).unwrap(), values);
+        }
+    }
+
+    #[test]
+    fn should_reject_unordered_types_and_malformed_keys() {
+        let row = test_record(
+            IntegerEncoding::Auto,
+            vec![(1, Value::Array(vec![1.into()])), (2, "a".into())],
+        );
+        let asc = (SortOrder::Asc, NullOrder::NullsFirst);
+        assert!(matches!(
+            row.sort_key(&[(1, asc.0, asc.1)]),
+            Err(ImprintError::InvalidSortKey(_))
+        ));
+
+        let key = row.sort_key(&[(2, asc.0, asc.1)]).unwrap();
+        let types = [(TypeCode::String, asc.0, asc.1)];
+        assert!(matches!(
+            decode_sort_key(&key[..key.len() - 1], &types),
+            Err(ImprintError::BufferUnderflow { .. })
+        ));
+        assert!(matches!(
+            decode_sort_key(&[key.as_ref(), &[0]].concat(), &types),
+            Err(ImprintError::InvalidSortKey(_))
+        ));
+    }
+
+    fn arb_float() -> impl Strategy<Value = f64> {
+        prop::num::f64::NORMAL
+            | prop::num::f64::SUBNORMAL
+            | prop::num::f64::ZERO
+            | prop::num::f64::INFINITE
+    }
+
+    proptest! {
+        #[test]
+        fn prop_key_order_matches_value_order(
+            a in (any::<i64>(), arb_float(), ".*", -10_000i128..10_000, 0u8..5),
+            b in (any::<i64>(), arb_float(), ".*", -10_000i128..10_000, 0u8..5),
+            order in prop_oneof![Just(SortOrder::Asc), Just(SortOrder::Desc)],
+        ) {
+            let row = |(int, float, string, unscaled, scale): &(i64, f64, String, i128, u8)| {
+                test_record(IntegerEncoding::Auto, vec![
+                    (1, (*int).into()),
+                    (2, (*float).into()),
+                    (3, string.clone().into()),
+                    (4, Decimal::new(*unscaled, 10, *scale).unwrap().into()),
+                ])
+            };
+            let (row_a, row_b) = (row(&a), row(&b));
+            let decimal = |(_, _, _, unscaled, scale): &(i64, f64, String, i128, u8)| {
+                Decimal::new(*unscaled, 10, *scale).unwrap()
+            };
+
+            let cases = [
+                (1, a.0.cmp(&b.0)),
+                (2, a.1.partial_cmp(&b.1).unwrap()),
+                (3, a.2.cmp(&b.2)),
+                (4, decimal(&a).cmp(&decimal(&b))),
+            ];
+            for (field_id, expected) in cases {
+                let fields = [(field_id, order, NullOrder::NullsFirst)];
+                let actual = row_a.sort_key(&fields).unwrap().cmp(&row_b.sort_key(&fields).unwrap());
+                let expected = match order {
+                    SortOrder::Asc => expected,
+                    SortOrder::Desc => expected.reverse(),
+                };
+                prop_assert_eq!(actual, expected, "field {}", field_id);
+            }
+        }
+    }
+}
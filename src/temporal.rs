@@ -0,0 +1,308 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{error::ImprintError, types::Value};
+
+const NANOS_PER_DAY: i64 = 86_400_000_000_000;
+
+/// Resolution of a [`Timestamp`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum TimeUnit {
+    Seconds = 0x0,
+    Millis = 0x1,
+    Micros = 0x2,
+    Nanos = 0x3,
+}
+
+impl TimeUnit {
+    pub(crate) fn nanos(&self) -> i128 {
+        match self {
+            Self::Seconds => 1_000_000_000,
+            Self::Millis => 1_000_000,
+            Self::Micros => 1_000,
+            Self::Nanos => 1,
+        }
+    }
+}
+
+impl TryFrom<u8> for TimeUnit {
+    type Error = ImprintError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(Self::Seconds),
+            0x1 => Ok(Self::Millis),
+            0x2 => Ok(Self::Micros),
+            0x3 => Ok(Self::Nanos),
+            _ => Err(ImprintError::SchemaError(format!(
+                "invalid time unit: {:#x}",
+                value
+            ))),
+        }
+    }
+}
+
+/// A point in time counted in `unit`s since the Unix epoch. Timestamps that
+/// aren't `utc` are wall-clock times in an unspecified local time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timestamp {
+    pub value: i64,
+    pub unit: TimeUnit,
+    pub utc: bool,
+}
+
+impl Timestamp {
+    /// Bit of the metadata byte marking a timestamp as UTC
+    const UTC_BIT: u8 = 0x80;
+
+    pub fn new(value: i64, unit: TimeUnit, utc: bool) -> Self {
+        Self { value, unit, utc }
+    }
+
+    /// Nanoseconds since the Unix epoch, which can exceed i64 for coarse units
+    pub fn as_nanos(&self) -> i128 {
+        self.value as i128 * self.unit.nanos()
+    }
+
+    /// The metadata byte written ahead of the value: the unit and the UTC flag
+    pub(crate) fn metadata(&self) -> u8 {
+        self.unit as u8 | if self.utc { Self::UTC_BIT } else { 0 }
+    }
+
+    pub(crate) fn from_metadata(metadata: u8, value: i64) -> Result<Self, ImprintError> {
+        Ok(Self {
+            value,
+            unit: TimeUnit::try_from(metadata & !Self::UTC_BIT)?,
+            utc: metadata & Self::UTC_BIT != 0,
+        })
+    }
+}
+
+impl TryFrom<SystemTime> for Timestamp {
+    type Error = ImprintError;
+
+    /// Fails for times more than about 292,000 years from the epoch, which don't
+    /// fit in 64 bits of microseconds
+    fn try_from(t: SystemTime) -> Result<Self, Self::Error> {
+        let (duration, before_epoch) = match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => (d, false),
+            Err(e) => (e.duration(), true),
+        };
+        let micros = duration.as_micros();
+        let micros = i64::try_from(micros).map_err(|_| ImprintError::Overflow {
+            what: "timestamp",
+            value: u64::try_from(micros).unwrap_or(u64::MAX),
+        })?;
+        let micros = if before_epoch { -micros } else { micros };
+        Ok(Timestamp::new(micros, TimeUnit::Micros, true))
+    }
+}
+
+impl TryFrom<Timestamp> for SystemTime {
+    type Error = ImprintError;
+
+    fn try_from(t: Timestamp) -> Result<Self, Self::Error> {
+        let nanos = t.as_nanos();
+        let out_of_range = || ImprintError::SchemaError(format!("timestamp out of range: {:?}", t));
+        let offset =
+            Duration::from_nanos(u64::try_from(nanos.unsigned_abs()).map_err(|_| out_of_range())?);
+        if nanos >= 0 {
+            UNIX_EPOCH.checked_add(offset)
+        } else {
+            UNIX_EPOCH.checked_sub(offset)
+        }
+        .ok_or_else(out_of_range)
+    }
+}
+
+impl From<Timestamp> for Value {
+    fn from(t: Timestamp) -> Value {
+        Value::Timestamp(t)
+    }
+}
+
+impl TryFrom<SystemTime> for Value {
+    type Error = ImprintError;
+
+    fn try_from(t: SystemTime) -> Result<Self, Self::Error> {
+        Ok(Value::Timestamp(t.try_into()?))
+    }
+}
+
+/// Check that a time of day in nanoseconds since midnight is within a single day
+pub(crate) fn check_time_of_day(nanos: i64) -> Result<i64, ImprintError> {
+    if (0..NANOS_PER_DAY).contains(&nanos) {
+        Ok(nanos)
+    } else {
+        Err(ImprintError::SchemaError(format!(
+            "time of day out of range: {}ns",
+            nanos
+        )))
+    }
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_impls {
+    use super::*;
+    use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+
+    const UNIX_EPOCH_DAYS_FROM_CE: i32 = 719_163;
+
+    impl From<DateTime<Utc>> for Value {
+        fn from(t: DateTime<Utc>) -> Value {
+            Value::Timestamp(Timestamp::new(t.timestamp_micros(), TimeUnit::Micros, true))
+        }
+    }
+
+    impl From<NaiveDateTime> for Value {
+        fn from(t: NaiveDateTime) -> Value {
+            Value::Timestamp(Timestamp::new(
+                t.and_utc().timestamp_micros(),
+                TimeUnit::Micros,
+                false,
+            ))
+        }
+    }
+
+    impl From<NaiveDate> for Value {
+        fn from(d: NaiveDate) -> Value {
+            Value::Date(chrono::Datelike::num_days_from_ce(&d) - UNIX_EPOCH_DAYS_FROM_CE)
+        }
+    }
+
+    impl From<NaiveTime> for Value {
+        fn from(t: NaiveTime) -> Value {
+            // leap seconds are folded into the last second of the day
+            let nanos = t.num_seconds_from_midnight() as i64 * 1_000_000_000
+                + (t.nanosecond() % 1_000_000_000) as i64;
+            Value::Time(nanos)
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+mod time_impls {
+    use super::*;
+    use time::{Date, OffsetDateTime, PrimitiveDateTime, Time};
+
+    const UNIX_EPOCH_JULIAN_DAY: i32 = 2_440_588;
+
+    impl From<OffsetDateTime> for Value {
+        fn from(t: OffsetDateTime) -> Value {
+            let micros = (t.unix_timestamp_nanos() / 1_000) as i64;
+            Value::Timestamp(Timestamp::new(micros, TimeUnit::Micros, true))
+        }
+    }
+
+    impl From<PrimitiveDateTime> for Value {
+        fn from(t: PrimitiveDateTime) -> Value {
+            let micros = (t.assume_utc().unix_timestamp_nanos() / 1_000) as i64;
+            Value::Timestamp(Timestamp::new(micros, TimeUnit::Micros, false))
+        }
+    }
+
+    impl From<Date> for Value {
+        fn from(d: Date) -> Value {
+            Value::Date(d.to_julian_day() - UNIX_EPOCH_JULIAN_DAY)
+        }
+    }
+
+    impl From<Time> for Value {
+        fn from(t: Time) -> Value {
+            let (h, m, s, ns) = t.as_hms_nano();
+            Value::Time((h as i64 * 3_600 + m as i64 * 60 + s as i64) * 1_000_000_000 + ns as i64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_roundtrip_system_time() {
+        // Given times before and after the epoch
+        for t in [
+            UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456),
+            UNIX_EPOCH - Duration::from_micros(86_400_000_001),
+        ] {
+            // When converting to a timestamp and back
+            let ts = Timestamp::try_from(t).unwrap();
+
+            // Then the time should be preserved at microsecond resolution
+            assert_eq!(ts.unit, TimeUnit::Micros);
+            assert!(ts.utc);
+            assert_eq!(SystemTime::try_from(ts).unwrap(), t);
+        }
+
+        // And times too far from the epoch for 64 bits of microseconds should be rejected
+        let distant = Duration::from_micros(u64::MAX);
+        for t in [
+            UNIX_EPOCH.checked_add(distant),
+            UNIX_EPOCH.checked_sub(distant),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            assert!(matches!(
+                Timestamp::try_from(t),
+                Err(ImprintError::Overflow { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn should_roundtrip_metadata() {
+        for unit in [
+            TimeUnit::Seconds,
+            TimeUnit::Millis,
+            TimeUnit::Micros,
+            TimeUnit::Nanos,
+        ] {
+            for utc in [true, false] {
+                let ts = Timestamp::new(-5, unit, utc);
+                assert_eq!(Timestamp::from_metadata(ts.metadata(), -5).unwrap(), ts);
+            }
+        }
+        assert!(Timestamp::from_metadata(0x04, 0).is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn should_convert_chrono_types() {
+        use chrono::{NaiveDate, NaiveTime};
+
+        let date = NaiveDate::from_ymd_opt(1970, 1, 2).unwrap();
+        assert_eq!(Value::from(date), Value::Date(1));
+
+        let time = NaiveTime::from_hms_nano_opt(0, 0, 1, 5).unwrap();
+        assert_eq!(Value::from(time), Value::Time(1_000_000_005));
+
+        let dt = date.and_time(time).and_utc();
+        assert_eq!(
+            Value::from(dt),
+            Value::Timestamp(Timestamp::new(86_401_000_000, TimeUnit::Micros, true))
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn should_convert_time_types() {
+        use time::{Date, Month, Time};
+
+        let date = Date::from_calendar_date(1969, Month::December, 31).unwrap();
+        assert_eq!(Value::from(date), Value::Date(-1));
+
+        let time = Time::from_hms_nano(0, 0, 1, 5).unwrap();
+        assert_eq!(Value::from(time), Value::Time(1_000_000_005));
+
+        let dt = Date::from_calendar_date(1970, Month::January, 2)
+            .unwrap()
+            .with_time(time)
+            .assume_utc();
+        assert_eq!(
+            Value::from(dt),
+            Value::Timestamp(Timestamp::new(86_401_000_000, TimeUnit::Micros, true))
+        );
+    }
+}
@@ -1,7 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
 
+use crate::compression::Compression;
+use crate::decimal::Decimal;
 use crate::error::ImprintError;
 use crate::serde::ValueRead;
+use crate::temporal::Timestamp;
 use bytes::Bytes;
 
 /// Magic byte that starts every Imprint record (ASCII 'I')
@@ -16,6 +20,14 @@ pub struct Flags(pub(crate) u8);
 impl Flags {
     /// Whether a field directory is present in the record
     pub const FIELD_DIRECTORY: u8 = 0x01;
+    /// Whether the payload is compressed (the codec is stored with the payload)
+    pub const COMPRESSED: u8 = 0x02;
+    /// Whether a CRC32C checksum of the record follows the payload
+    pub const CHECKSUM: u8 = 0x04;
+    /// Whether the field directory uses the compact varint encoding
+    pub const COMPACT_DIRECTORY: u8 = 0x08;
+    /// Whether directory entries record the length of each field
+    pub const FIELD_LENGTHS: u8 = 0x10;
 
     pub fn new(flags: u8) -> Self {
         Self(flags)
@@ -24,6 +36,31 @@ impl Flags {
     pub fn has_field_directory(&self) -> bool {
         self.0 & Self::FIELD_DIRECTORY != 0
     }
+
+    pub fn has_compression(&self) -> bool {
+        self.0 & Self::COMPRESSED != 0
+    }
+
+    pub fn has_checksum(&self) -> bool {
+        self.0 & Self::CHECKSUM != 0
+    }
+
+    pub fn has_compact_directory(&self) -> bool {
+        self.0 & Self::COMPACT_DIRECTORY != 0
+    }
+
+    pub fn has_field_lengths(&self) -> bool {
+        self.0 & Self::FIELD_LENGTHS != 0
+    }
+
+    /// Returns a copy of these flags with the given bits set or cleared
+    pub(crate) fn with(self, bits: u8, enabled: bool) -> Self {
+        if enabled {
+            Self(self.0 | bits)
+        } else {
+            Self(self.0 & !bits)
+        }
+    }
 }
 
 /// Type codes for field values
@@ -41,6 +78,30 @@ pub enum TypeCode {
     Array = 0x8,
     Map = 0x9,
     Row = 0xA,
+    Timestamp = 0xB,
+    Date = 0xC,
+    Time = 0xD,
+    Decimal = 0xE,
+    Uuid = 0xF,
+    FixedBytes = 0x10,
+    Enum = 0x11,
+    Int8 = 0x12,
+    Int16 = 0x13,
+    UInt8 = 0x14,
+    UInt16 = 0x15,
+    UInt32 = 0x16,
+    UInt64 = 0x17,
+    /// Int32 written as a zigzag varint
+    VarInt32 = 0x18,
+    /// Int64 written as a zigzag varint
+    VarInt64 = 0x19,
+    /// UInt32 written as a LEB128 varint
+    VarUInt32 = 0x1A,
+    /// UInt64 written as a LEB128 varint
+    VarUInt64 = 0x1B,
+    Union = 0x1C,
+    /// Marks a field as deleted, so that merging removes it. Has no payload.
+    Tombstone = 0x1D,
 }
 
 impl TypeCode {
@@ -49,9 +110,59 @@ impl TypeCode {
             Self::Bool => Some(1),
             Self::Int32 | Self::Float32 => Some(4),
             Self::Int64 | Self::Float64 => Some(8),
+            Self::Timestamp => Some(9),
+            Self::Date => Some(4),
+            Self::Time => Some(8),
+            Self::Decimal => Some(18),
+            Self::Uuid => Some(16),
+            Self::Int8 | Self::UInt8 => Some(1),
+            Self::Int16 | Self::UInt16 => Some(2),
+            Self::UInt32 => Some(4),
+            Self::UInt64 => Some(8),
             _ => None,
         }
     }
+
+    /// The type of the values read for this type code, which differs from it
+    /// only for the varint encodings of integers
+    pub fn logical(&self) -> TypeCode {
+        match self {
+            Self::VarInt32 => Self::Int32,
+            Self::VarInt64 => Self::Int64,
+            Self::VarUInt32 => Self::UInt32,
+            Self::VarUInt64 => Self::UInt64,
+            other => *other,
+        }
+    }
+
+    /// Bit width and signedness of integer type codes
+    fn integer_width(&self) -> Option<(u32, bool)> {
+        match self {
+            Self::Int8 => Some((8, true)),
+            Self::Int16 => Some((16, true)),
+            Self::Int32 => Some((32, true)),
+            Self::Int64 => Some((64, true)),
+            Self::UInt8 => Some((8, false)),
+            Self::UInt16 => Some((16, false)),
+            Self::UInt32 => Some((32, false)),
+            Self::UInt64 => Some((64, false)),
+            _ => None,
+        }
+    }
+
+    /// Whether every value of this type can be converted to `target` without loss,
+    /// e.g. `Int32` to `Int64` or `UInt32` to `Int64` but not `Int32` to `UInt64`.
+    pub fn widens_to(&self, target: TypeCode) -> bool {
+        if *self == target {
+            return true;
+        }
+        match (self.integer_width(), target.integer_width()) {
+            (Some((from, from_signed)), Some((to, to_signed))) => {
+                to > from && (to_signed || !from_signed)
+            }
+            _ => *self == Self::Float32 && target == Self::Float64,
+        }
+    }
 }
 
 impl TryFrom<u8> for TypeCode {
@@ -70,6 +181,25 @@ impl TryFrom<u8> for TypeCode {
             0x8 => Ok(Self::Array),
             0x9 => Ok(Self::Map),
             0xA => Ok(Self::Row),
+            0xB => Ok(Self::Timestamp),
+            0xC => Ok(Self::Date),
+            0xD => Ok(Self::Time),
+            0xE => Ok(Self::Decimal),
+            0xF => Ok(Self::Uuid),
+            0x10 => Ok(Self::FixedBytes),
+            0x11 => Ok(Self::Enum),
+            0x12 => Ok(Self::Int8),
+            0x13 => Ok(Self::Int16),
+            0x14 => Ok(Self::UInt8),
+            0x15 => Ok(Self::UInt16),
+            0x16 => Ok(Self::UInt32),
+            0x17 => Ok(Self::UInt64),
+            0x18 => Ok(Self::VarInt32),
+            0x19 => Ok(Self::VarInt64),
+            0x1a => Ok(Self::VarUInt32),
+            0x1b => Ok(Self::VarUInt64),
+            0x1c => Ok(Self::Union),
+            0x1d => Ok(Self::Tombstone),
             _ => Err(ImprintError::InvalidFieldType(value)),
         }
     }
@@ -89,6 +219,30 @@ pub enum Value {
     Array(Vec<Value>),
     Map(HashMap<MapKey, Value>),
     Row(Box<ImprintRecord>),
+    Timestamp(Timestamp),
+    /// Days since the Unix epoch
+    Date(i32),
+    /// Nanoseconds since midnight
+    Time(i64),
+    Decimal(Decimal),
+    /// An RFC 4122 UUID in its big-endian byte order
+    Uuid([u8; 16]),
+    /// Bytes whose size is fixed by the field rather than written with each value
+    FixedBytes(Vec<u8>),
+    /// Ordinal of an enum symbol, resolved to a name through the fieldspace
+    Enum(u32),
+    Int8(i8),
+    Int16(i16),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
+    /// A field that may hold one of several types, such as a string or an int.
+    /// The type code of the inner value identifies the active branch.
+    Union(Box<Value>),
+    /// The field has been deleted. Only valid as the value of a top-level field,
+    /// where [`Merge`](crate::Merge) removes the field from the result.
+    Tombstone,
 }
 
 impl Value {
@@ -105,6 +259,76 @@ impl Value {
             Self::Array(_) => TypeCode::Array,
             Self::Map(_) => TypeCode::Map,
             Self::Row(_) => TypeCode::Row,
+            Self::Timestamp(_) => TypeCode::Timestamp,
+            Self::Date(_) => TypeCode::Date,
+            Self::Time(_) => TypeCode::Time,
+            Self::Decimal(_) => TypeCode::Decimal,
+            Self::Uuid(_) => TypeCode::Uuid,
+            Self::FixedBytes(_) => TypeCode::FixedBytes,
+            Self::Enum(_) => TypeCode::Enum,
+            Self::Int8(_) => TypeCode::Int8,
+            Self::Int16(_) => TypeCode::Int16,
+            Self::UInt8(_) => TypeCode::UInt8,
+            Self::UInt16(_) => TypeCode::UInt16,
+            Self::UInt32(_) => TypeCode::UInt32,
+            Self::UInt64(_) => TypeCode::UInt64,
+            Self::Union(_) => TypeCode::Union,
+            Self::Tombstone => TypeCode::Tombstone,
+        }
+    }
+
+    /// The type code of the active branch if this is a union
+    pub fn union_branch(&self) -> Option<TypeCode> {
+        match self {
+            Self::Union(inner) => Some(inner.type_code()),
+            _ => None,
+        }
+    }
+
+    /// Convert this value to the given type if that loses nothing, see
+    /// [`TypeCode::widens_to`]. Lets a reader that expects an `Int64` accept an
+    /// `Int32` field, for example.
+    pub fn widen(self, target: TypeCode) -> Result<Value, ImprintError> {
+        let actual = self.type_code();
+        if actual == target {
+            return Ok(self);
+        }
+        if !actual.widens_to(target) {
+            return Err(ImprintError::TypeMismatch {
+                expected: target,
+                actual,
+            });
+        }
+        if let Self::Float32(f) = self {
+            return Ok(Self::Float64(f as f64));
+        }
+
+        let Some(i) = self.as_integer() else {
+            unreachable!("only integers and floats widen");
+        };
+        // the target is wider than the source, so these casts never truncate
+        Ok(match target {
+            TypeCode::Int16 => Self::Int16(i as i16),
+            TypeCode::Int32 => Self::Int32(i as i32),
+            TypeCode::Int64 => Self::Int64(i as i64),
+            TypeCode::UInt16 => Self::UInt16(i as u16),
+            TypeCode::UInt32 => Self::UInt32(i as u32),
+            TypeCode::UInt64 => Self::UInt64(i as u64),
+            _ => unreachable!("no integer widens to {:?}", target),
+        })
+    }
+
+    pub(crate) fn as_integer(&self) -> Option<i128> {
+        match *self {
+            Self::Int8(i) => Some(i as i128),
+            Self::Int16(i) => Some(i as i128),
+            Self::Int32(i) => Some(i as i128),
+            Self::Int64(i) => Some(i as i128),
+            Self::UInt8(i) => Some(i as i128),
+            Self::UInt16(i) => Some(i as i128),
+            Self::UInt32(i) => Some(i as i128),
+            Self::UInt64(i) => Some(i as i128),
+            _ => None,
         }
     }
 
@@ -132,6 +356,38 @@ impl From<i64> for Value {
     }
 }
 
+impl From<i8> for Value {
+    fn from(i: i8) -> Value {
+        Value::Int8(i)
+    }
+}
+
+impl From<i16> for Value {
+    fn from(i: i16) -> Value {
+        Value::Int16(i)
+    }
+}
+
+// there is no `From<u8>`, which would make `Vec<u8>` ambiguous between bytes and an array
+
+impl From<u16> for Value {
+    fn from(i: u16) -> Value {
+        Value::UInt16(i)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(i: u32) -> Value {
+        Value::UInt32(i)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(i: u64) -> Value {
+        Value::UInt64(i)
+    }
+}
+
 impl From<f32> for Value {
     fn from(f: f32) -> Value {
         Value::Float32(f)
@@ -180,6 +436,12 @@ impl<K: Into<MapKey>, V: Into<Value>> From<HashMap<K, V>> for Value {
     }
 }
 
+impl<K: Into<MapKey>, V: Into<Value>> From<BTreeMap<K, V>> for Value {
+    fn from(m: BTreeMap<K, V>) -> Value {
+        Value::Map(m.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
 impl From<Box<ImprintRecord>> for Value {
     fn from(r: Box<ImprintRecord>) -> Value {
         Value::Row(r)
@@ -199,6 +461,14 @@ impl From<MapKey> for Value {
             MapKey::Int64(i) => Value::Int64(i),
             MapKey::Bytes(b) => Value::Bytes(b),
             MapKey::String(s) => Value::String(s),
+            MapKey::Bool(b) => Value::Bool(b),
+            MapKey::Uuid(u) => Value::Uuid(u),
+            MapKey::Int8(i) => Value::Int8(i),
+            MapKey::Int16(i) => Value::Int16(i),
+            MapKey::UInt8(i) => Value::UInt8(i),
+            MapKey::UInt16(i) => Value::UInt16(i),
+            MapKey::UInt32(i) => Value::UInt32(i),
+            MapKey::UInt64(i) => Value::UInt64(i),
         }
     }
 }
@@ -210,26 +480,49 @@ impl PartialEq<MapKey> for Value {
     }
 }
 
-/// A subset of `Value` that’s valid as a map key.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A subset of `Value` that’s valid as a map key. Keys are ordered by their
+/// value, which is the order map entries are written in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum MapKey {
+    Bool(bool),
     Int32(i32),
     Int64(i64),
     Bytes(Vec<u8>),
     String(String),
+    Uuid([u8; 16]),
+    Int8(i8),
+    Int16(i16),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
 }
 
 impl MapKey {
     pub fn type_code(&self) -> TypeCode {
         match self {
+            MapKey::Bool(_) => TypeCode::Bool,
             MapKey::Int32(_) => TypeCode::Int32,
             MapKey::Int64(_) => TypeCode::Int64,
             MapKey::Bytes(_) => TypeCode::Bytes,
             MapKey::String(_) => TypeCode::String,
+            MapKey::Uuid(_) => TypeCode::Uuid,
+            MapKey::Int8(_) => TypeCode::Int8,
+            MapKey::Int16(_) => TypeCode::Int16,
+            MapKey::UInt8(_) => TypeCode::UInt8,
+            MapKey::UInt16(_) => TypeCode::UInt16,
+            MapKey::UInt32(_) => TypeCode::UInt32,
+            MapKey::UInt64(_) => TypeCode::UInt64,
         }
     }
 }
 
+impl From<bool> for MapKey {
+    fn from(b: bool) -> MapKey {
+        MapKey::Bool(b)
+    }
+}
+
 impl From<i32> for MapKey {
     fn from(i: i32) -> MapKey {
         MapKey::Int32(i)
@@ -242,6 +535,42 @@ impl From<i64> for MapKey {
     }
 }
 
+impl From<i8> for MapKey {
+    fn from(i: i8) -> MapKey {
+        MapKey::Int8(i)
+    }
+}
+
+impl From<i16> for MapKey {
+    fn from(i: i16) -> MapKey {
+        MapKey::Int16(i)
+    }
+}
+
+impl From<u8> for MapKey {
+    fn from(i: u8) -> MapKey {
+        MapKey::UInt8(i)
+    }
+}
+
+impl From<u16> for MapKey {
+    fn from(i: u16) -> MapKey {
+        MapKey::UInt16(i)
+    }
+}
+
+impl From<u32> for MapKey {
+    fn from(i: u32) -> MapKey {
+        MapKey::UInt32(i)
+    }
+}
+
+impl From<u64> for MapKey {
+    fn from(i: u64) -> MapKey {
+        MapKey::UInt64(i)
+    }
+}
+
 impl From<Vec<u8>> for MapKey {
     fn from(b: Vec<u8>) -> MapKey {
         MapKey::Bytes(b)
@@ -260,6 +589,20 @@ impl From<&str> for MapKey {
     }
 }
 
+#[cfg(feature = "uuid")]
+impl From<uuid::Uuid> for Value {
+    fn from(u: uuid::Uuid) -> Value {
+        Value::Uuid(u.into_bytes())
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<uuid::Uuid> for MapKey {
+    fn from(u: uuid::Uuid) -> MapKey {
+        MapKey::Uuid(u.into_bytes())
+    }
+}
+
 impl TryFrom<Value> for MapKey {
     type Error = ImprintError;
 
@@ -269,6 +612,14 @@ impl TryFrom<Value> for MapKey {
             Value::Int64(i) => Ok(MapKey::Int64(i)),
             Value::Bytes(b) => Ok(MapKey::Bytes(b)),
             Value::String(s) => Ok(MapKey::String(s)),
+            Value::Bool(b) => Ok(MapKey::Bool(b)),
+            Value::Uuid(u) => Ok(MapKey::Uuid(u)),
+            Value::Int8(i) => Ok(MapKey::Int8(i)),
+            Value::Int16(i) => Ok(MapKey::Int16(i)),
+            Value::UInt8(i) => Ok(MapKey::UInt8(i)),
+            Value::UInt16(i) => Ok(MapKey::UInt16(i)),
+            Value::UInt32(i) => Ok(MapKey::UInt32(i)),
+            Value::UInt64(i) => Ok(MapKey::UInt64(i)),
             other => Err(ImprintError::InvalidFieldType(other.type_code() as u8)),
         }
     }
@@ -282,13 +633,21 @@ impl PartialEq<Value> for MapKey {
             (MapKey::Int64(a), Value::Int64(b)) => a == b,
             (MapKey::Bytes(a), Value::Bytes(b)) => a == b,
             (MapKey::String(a), Value::String(b)) => a == b,
+            (MapKey::Bool(a), Value::Bool(b)) => a == b,
+            (MapKey::Uuid(a), Value::Uuid(b)) => a == b,
+            (MapKey::Int8(a), Value::Int8(b)) => a == b,
+            (MapKey::Int16(a), Value::Int16(b)) => a == b,
+            (MapKey::UInt8(a), Value::UInt8(b)) => a == b,
+            (MapKey::UInt16(a), Value::UInt16(b)) => a == b,
+            (MapKey::UInt32(a), Value::UInt32(b)) => a == b,
+            (MapKey::UInt64(a), Value::UInt64(b)) => a == b,
             _ => false,
         }
     }
 }
 
 /// A directory entry describing a single field in an Imprint record.
-/// Each entry has a fixed size of 9 bytes.
+/// Each entry has a fixed size of 9 bytes, or 13 bytes when lengths are recorded.
 #[derive(Debug, Clone, PartialEq)]
 pub struct DirectoryEntry {
     /// Uniquely assigned identifier within a fieldspace (4 bytes)
@@ -297,10 +656,14 @@ pub struct DirectoryEntry {
     pub type_code: TypeCode,
     /// Byte position of the value relative to the payload (4 bytes)
     pub offset: u32,
+    /// Number of payload bytes holding the value (4 bytes, only written when
+    /// it can't be derived from the offsets of the other fields). Entries read on
+    /// their own don't know it, so it's only set once the whole directory is read.
+    pub(crate) length: u32,
 }
 
 /// A schema identifier consisting of a fieldspace ID and schema hash
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SchemaId {
     pub fieldspace_id: u32,
     pub schema_hash: u32,
@@ -314,41 +677,177 @@ pub struct Header {
     pub payload_size: u32,
 }
 
+/// The payload of a record. Payloads read in compressed form are only
+/// decompressed the first time their bytes are needed.
+#[derive(Debug, Clone)]
+pub(crate) struct Payload {
+    compression: Compression,
+    /// The compressed bytes as read from the wire, if any
+    compressed: Option<Bytes>,
+    /// Size of the payload once decompressed
+    size: usize,
+    pub(crate) decompressed: OnceLock<Bytes>,
+}
+
+impl Payload {
+    /// Creates a payload from uncompressed bytes, to be compressed with `compression` on write
+    pub(crate) fn new(bytes: Bytes, compression: Compression) -> Self {
+        Self {
+            compression,
+            compressed: None,
+            size: bytes.len(),
+            decompressed: OnceLock::from(bytes),
+        }
+    }
+
+    /// Creates a payload from bytes compressed with `compression` that decompress to `size` bytes
+    pub(crate) fn compressed(bytes: Bytes, size: usize, compression: Compression) -> Self {
+        Self {
+            compression,
+            compressed: Some(bytes),
+            size,
+            decompressed: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Size of the payload once decompressed
+    pub(crate) fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Get the decompressed payload bytes, decompressing them on first access
+    pub(crate) fn get(&self) -> Result<&Bytes, ImprintError> {
+        if let Some(bytes) = self.decompressed.get() {
+            return Ok(bytes);
+        }
+
+        // the compressed bytes are always present when the cell is empty
+        let compressed = self.compressed.as_ref().expect("payload has no bytes");
+        let bytes = self.compression.decompress(compressed, self.size)?;
+        Ok(self.decompressed.get_or_init(|| bytes))
+    }
+
+    /// Get the payload bytes compressed with this payload's codec, reusing
+    /// the bytes read from the wire when possible
+    pub(crate) fn get_compressed(&self) -> Result<Bytes, ImprintError> {
+        match &self.compressed {
+            Some(bytes) => Ok(bytes.clone()),
+            None => self.compression.compress(self.get()?),
+        }
+    }
+}
+
+impl PartialEq for Payload {
+    fn eq(&self, other: &Self) -> bool {
+        if self.compression != other.compression || self.size != other.size {
+            return false;
+        }
+        match (self.get(), other.get()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => self.compressed == other.compressed,
+        }
+    }
+}
+
 /// An Imprint record containing a header, optional field directory, and payload
 #[derive(Debug, Clone, PartialEq)]
 pub struct ImprintRecord {
     pub(crate) header: Header,
     pub(crate) directory: Vec<DirectoryEntry>,
-    pub(crate) payload: Bytes,
+    pub(crate) payload: Payload,
 }
 
 impl ImprintRecord {
     /// Get a value by field ID, deserializing it on demand
     pub fn get_value(&self, field_id: u32) -> Result<Option<Value>, ImprintError> {
-        match self.directory.binary_search_by_key(&field_id, |e| e.id) {
-            Ok(idx) => {
-                let entry = &self.directory[idx];
-                let value_bytes = self.payload.slice(entry.offset as usize..);
-                let (value, _) = Value::read(entry.type_code, value_bytes)?;
-                Ok(Some(value))
-            }
-            Err(_) => Ok(None),
-        }
+        let Some(entry) = self.entry(field_id) else {
+            return Ok(None);
+        };
+        // values are read from their exact range, which is what bounds fixed-size bytes
+        let (value, _) = Value::read(entry.type_code, self.entry_bytes(entry)?)?;
+        Ok(Some(value))
+    }
+
+    /// Get a value by field ID as the given type, widening it if it was written
+    /// as a narrower one. See [`Value::widen`].
+    pub fn get_value_as(
+        &self,
+        field_id: u32,
+        type_code: TypeCode,
+    ) -> Result<Option<Value>, ImprintError> {
+        self.get_value(field_id)?
+            .map(|value| value.widen(type_code))
+            .transpose()
     }
 
     /// Get the raw bytes for a field without deserializing
-    pub fn get_raw_bytes(&self, field_id: u32) -> Option<Bytes> {
-        let idx = self
-            .directory
+    pub fn get_raw_bytes(&self, field_id: u32) -> Result<Option<Bytes>, ImprintError> {
+        self.entry(field_id)
+            .map(|entry| self.entry_bytes(entry))
+            .transpose()
+    }
+
+    pub(crate) fn entry(&self, field_id: u32) -> Option<&DirectoryEntry> {
+        self.directory
             .binary_search_by_key(&field_id, |e| e.id)
-            .ok()?;
-        let entry = &self.directory[idx];
+            .ok()
+            .map(|idx| &self.directory[idx])
+    }
+
+    pub(crate) fn entry_bytes(&self, entry: &DirectoryEntry) -> Result<Bytes, ImprintError> {
+        let payload = self.payload.get()?;
         let start = entry.offset as usize;
-        let next_offset = self.directory[idx + 1..]
-            .first()
-            .map(|e| e.offset as usize)
-            .unwrap_or(self.payload.len());
-        Some(self.payload.slice(start..next_offset))
+        let end = start + entry.length as usize;
+        if end > payload.len() {
+            return Err(ImprintError::BufferUnderflow {
+                needed: end,
+                available: payload.len(),
+            });
+        }
+        Ok(payload.slice(start..end))
+    }
+
+    /// Number of payload bytes not referenced by the directory, such as zombie data
+    /// left behind by merges. These can be reclaimed with [`Compact`](crate::Compact).
+    pub fn wasted_bytes(&self) -> usize {
+        let referenced: usize = self.directory.iter().map(|e| e.length as usize).sum();
+        self.payload.len().saturating_sub(referenced)
+    }
+
+    /// The codec used to compress this record's payload when it is written
+    pub fn compression(&self) -> Compression {
+        self.payload.compression()
+    }
+
+    /// Change the codec used to compress this record's payload when it is written
+    pub fn set_compression(&mut self, compression: Compression) -> Result<(), ImprintError> {
+        if compression != self.payload.compression() {
+            self.payload = Payload::new(self.payload.get()?.clone(), compression);
+            self.header.flags = self
+                .header
+                .flags
+                .with(Flags::COMPRESSED, compression != Compression::None);
+        }
+        Ok(())
+    }
+
+    /// Whether a checksum is appended to this record when it is written
+    pub fn has_checksum(&self) -> bool {
+        self.header.flags.has_checksum()
+    }
+
+    /// Enable or disable appending a checksum to this record when it is written
+    pub fn set_checksum(&mut self, enabled: bool) {
+        self.header.flags = self.header.flags.with(Flags::CHECKSUM, enabled);
+    }
+
+    /// Enable or disable the compact directory encoding when this record is written
+    pub fn set_compact_directory(&mut self, enabled: bool) {
+        self.header.flags = self.header.flags.with(Flags::COMPACT_DIRECTORY, enabled);
     }
 }
 
@@ -365,4 +864,53 @@ mod tests {
     fn test_value_eq_map_key() {
         assert!(Value::String("foo".into()) == MapKey::String("foo".into()));
     }
+
+    #[test]
+    fn test_widen_integers() {
+        // Given integers that fit in a wider type
+        let cases = [
+            (Value::Int8(-1), TypeCode::Int64, Value::Int64(-1)),
+            (Value::Int32(-7), TypeCode::Int64, Value::Int64(-7)),
+            (Value::UInt8(255), TypeCode::Int16, Value::Int16(255)),
+            (
+                Value::UInt32(u32::MAX),
+                TypeCode::UInt64,
+                Value::UInt64(u32::MAX as u64),
+            ),
+            (
+                Value::UInt32(u32::MAX),
+                TypeCode::Int64,
+                Value::Int64(u32::MAX as i64),
+            ),
+            (Value::Float32(1.5), TypeCode::Float64, Value::Float64(1.5)),
+            (Value::Int16(3), TypeCode::Int16, Value::Int16(3)),
+        ];
+        for (value, target, expected) in cases {
+            assert_eq!(value.widen(target).unwrap(), expected);
+        }
+
+        // And conversions that could lose values or signs
+        let rejected = [
+            (Value::Int64(1), TypeCode::Int32),
+            (Value::Int32(1), TypeCode::UInt64),
+            (Value::UInt64(1), TypeCode::Int64),
+            (Value::UInt32(1), TypeCode::Int32),
+            (Value::Int32(1), TypeCode::Float64),
+            (Value::String("1".into()), TypeCode::Int64),
+        ];
+        for (value, target) in rejected {
+            assert!(matches!(
+                value.widen(target),
+                Err(ImprintError::TypeMismatch { .. })
+            ));
+        }
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn test_convert_uuid() {
+        let u = uuid::Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+        assert_eq!(Value::from(u), Value::Uuid(*u.as_bytes()));
+        assert!(MapKey::from(u) == Value::from(u));
+    }
 }
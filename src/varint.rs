@@ -1,49 +1,109 @@
 use crate::error::ImprintError;
-use bytes::{Buf, BufMut, Bytes, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
 
 const CONTINUATION_BIT: u8 = 0x80;
 const SEGMENT_BITS: u8 = 0x7f;
-const MAX_VARINT_LEN: usize = 5; // Enough for u32, which is our max use case
+const MAX_VARINT_LEN: usize = 5; // Enough for u32
+const MAX_VARINT64_LEN: usize = 10; // Enough for u64
+
+/// Number of bytes `value` takes once encoded as a VarInt
+pub fn encoded_len(value: u64) -> usize {
+    (64 - (value | 1).leading_zeros() as usize).div_ceil(7)
+}
 
 /// Encode a u32 as a VarInt into the provided buffer
 pub fn encode(value: u32, buf: &mut BytesMut) {
+    encode_u64(value as u64, buf)
+}
+
+/// Encode a u64 as a VarInt into the provided buffer
+pub fn encode_u64(value: u64, buf: &mut BytesMut) {
+    let mut bytes = [0; MAX_VARINT64_LEN];
+    let len = encode_u64_to(value, &mut bytes).expect("buffer fits any u64");
+    buf.put_slice(&bytes[..len]);
+}
+
+/// Encode an i64 as a zigzag VarInt into the provided buffer
+pub fn encode_i64(value: i64, buf: &mut BytesMut) {
+    encode_u64(zigzag_encode_i64(value), buf)
+}
+
+/// Encode a u32 as a VarInt at the start of `buf`, returning the number of bytes written
+pub fn encode_to(value: u32, buf: &mut [u8]) -> Result<usize, ImprintError> {
+    encode_u64_to(value as u64, buf)
+}
+
+/// Encode a u64 as a VarInt at the start of `buf`, returning the number of bytes written
+pub fn encode_u64_to(value: u64, buf: &mut [u8]) -> Result<usize, ImprintError> {
+    let len = encoded_len(value);
+    if buf.len() < len {
+        return Err(ImprintError::BufferUnderflow {
+            needed: len,
+            available: buf.len(),
+        });
+    }
+
     let mut val = value;
-    loop {
-        let mut byte = (val & (SEGMENT_BITS as u32)) as u8;
+    for byte in &mut buf[..len] {
+        *byte = (val & (SEGMENT_BITS as u64)) as u8 | CONTINUATION_BIT;
         val >>= 7;
-        if val != 0 {
-            byte |= CONTINUATION_BIT;
-        }
-        buf.put_u8(byte);
-        if val == 0 {
-            break;
-        }
     }
+    buf[len - 1] &= SEGMENT_BITS;
+    Ok(len)
+}
+
+/// Encode a length as a VarInt, failing if it exceeds the u32 lengths of the format
+pub(crate) fn encode_len(len: usize, buf: &mut BytesMut) -> Result<(), ImprintError> {
+    encode(checked_u32(len, "length")?, buf);
+    Ok(())
+}
+
+/// Convert a length or offset to the u32 the format stores it as
+pub(crate) fn checked_u32(value: usize, what: &'static str) -> Result<u32, ImprintError> {
+    u32::try_from(value).map_err(|_| ImprintError::Overflow {
+        what,
+        value: value as u64,
+    })
 }
 
 /// Decode a VarInt from the provided bytes, returning the value and number of bytes read
-pub fn decode(mut bytes: Bytes) -> Result<(u32, usize), ImprintError> {
-    let mut result: u32 = 0;
+pub fn decode(bytes: Bytes) -> Result<(u32, usize), ImprintError> {
+    decode_slice(&bytes)
+}
+
+/// Decode a VarInt from the start of a slice, returning the value and number of bytes read
+pub fn decode_slice(bytes: &[u8]) -> Result<(u32, usize), ImprintError> {
+    let (value, bytes_read) = decode_u64_slice(bytes)?;
+    if bytes_read > MAX_VARINT_LEN || value > u32::MAX as u64 {
+        return Err(ImprintError::InvalidVarInt);
+    }
+    Ok((value as u32, bytes_read))
+}
+
+/// Decode a 64-bit VarInt from the provided bytes, returning the value and number of bytes read
+pub fn decode_u64(bytes: Bytes) -> Result<(u64, usize), ImprintError> {
+    decode_u64_slice(&bytes)
+}
+
+/// Decode a zigzag encoded i64 VarInt from the provided bytes, returning the value and number of bytes read
+pub fn decode_i64(bytes: Bytes) -> Result<(i64, usize), ImprintError> {
+    let (value, bytes_read) = decode_u64_slice(&bytes)?;
+    Ok((zigzag_decode_i64(value), bytes_read))
+}
+
+/// Decode a 64-bit VarInt from the start of a slice, returning the value and number of bytes read
+pub fn decode_u64_slice(bytes: &[u8]) -> Result<(u64, usize), ImprintError> {
+    let mut result: u64 = 0;
     let mut shift = 0;
-    let mut bytes_read = 0;
 
-    loop {
-        if bytes_read >= MAX_VARINT_LEN {
+    for (bytes_read, &byte) in bytes.iter().enumerate() {
+        if bytes_read >= MAX_VARINT64_LEN {
             return Err(ImprintError::InvalidVarInt);
         }
-        if !bytes.has_remaining() {
-            return Err(ImprintError::BufferUnderflow {
-                needed: 1,
-                available: 0,
-            });
-        }
-
-        let byte = bytes.get_u8();
-        bytes_read += 1;
 
         // Check if adding these 7 bits would overflow
-        let segment = (byte & SEGMENT_BITS) as u32;
-        if shift >= 32 || (shift == 28 && segment > 0xF) {
+        let segment = (byte & SEGMENT_BITS) as u64;
+        if shift == 63 && segment > 0x1 {
             return Err(ImprintError::InvalidVarInt);
         }
 
@@ -52,13 +112,39 @@ pub fn decode(mut bytes: Bytes) -> Result<(u32, usize), ImprintError> {
 
         // If the high bit is not set, this is the last byte
         if byte & CONTINUATION_BIT == 0 {
-            break;
+            return Ok((result, bytes_read + 1));
         }
 
         shift += 7;
     }
 
-    Ok((result, bytes_read))
+    if bytes.len() >= MAX_VARINT64_LEN {
+        return Err(ImprintError::InvalidVarInt);
+    }
+    Err(ImprintError::BufferUnderflow {
+        needed: 1,
+        available: 0,
+    })
+}
+
+/// Map a signed value onto an unsigned one so that small magnitudes encode to few bytes
+pub fn zigzag_encode(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+/// Inverse of [`zigzag_encode`]
+pub fn zigzag_decode(value: u32) -> i32 {
+    ((value >> 1) as i32) ^ -((value & 1) as i32)
+}
+
+/// 64-bit variant of [`zigzag_encode`]
+pub fn zigzag_encode_i64(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Inverse of [`zigzag_encode_i64`]
+pub fn zigzag_decode_i64(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
 }
 
 #[cfg(test)]
@@ -152,4 +238,117 @@ mod tests {
         // Then it should return an invalid varint error
         assert!(matches!(decode(buf), Err(ImprintError::InvalidVarInt)));
     }
+
+    #[test]
+    fn should_roundtrip_u64_values() {
+        // Given values across the whole u64 range
+        for value in [0u64, 127, 128, u32::MAX as u64 + 1, 1 << 63, u64::MAX] {
+            // When encoding and then decoding the value
+            let mut buf = BytesMut::new();
+            encode_u64(value, &mut buf);
+            let (decoded, len) = decode_u64(buf.clone().freeze()).unwrap();
+
+            // Then the decoded value and length should match
+            assert_eq!(decoded, value, "Failed to roundtrip {}", value);
+            assert_eq!(len, buf.len());
+        }
+
+        // And values past u64 should be rejected
+        let mut too_large = vec![0xff; 9];
+        too_large.push(0x02);
+        assert!(matches!(
+            decode_u64(Bytes::from(too_large)),
+            Err(ImprintError::InvalidVarInt)
+        ));
+
+        // And zigzag should map the i64 extremes to the u64 extremes
+        assert_eq!(zigzag_encode_i64(i64::MIN), u64::MAX);
+        assert_eq!(zigzag_decode_i64(u64::MAX - 1), i64::MAX);
+        assert_eq!(zigzag_decode_i64(zigzag_encode_i64(-3)), -3);
+    }
+
+    #[test]
+    fn should_encode_and_decode_slices() {
+        // Given values of every encoded length
+        for value in [
+            0u64,
+            1 << 7,
+            1 << 14,
+            1 << 28,
+            u32::MAX as u64,
+            1 << 56,
+            u64::MAX,
+        ] {
+            // When encoding into a slice
+            let mut slice = [0xaa; 12];
+            let len = encode_u64_to(value, &mut slice).unwrap();
+
+            // Then it should match the buffer encoding and decode back from the slice
+            let mut buf = BytesMut::new();
+            encode_u64(value, &mut buf);
+            assert_eq!(&slice[..len], &buf[..]);
+            assert_eq!(len, encoded_len(value));
+            assert_eq!(decode_u64_slice(&slice).unwrap(), (value, len));
+        }
+
+        // And a slice too small for the value should be rejected rather than truncated
+        let mut slice = [0; 2];
+        assert!(matches!(
+            encode_to(1 << 14, &mut slice),
+            Err(ImprintError::BufferUnderflow { needed: 3, .. })
+        ));
+        assert_eq!(encode_to(300, &mut slice).unwrap(), 2);
+        assert_eq!(decode_slice(&slice).unwrap(), (300, 2));
+    }
+
+    #[test]
+    fn should_roundtrip_i64_values() {
+        for value in [0i64, -1, 63, -64, i64::MAX, i64::MIN] {
+            let mut buf = BytesMut::new();
+            encode_i64(value, &mut buf);
+            assert_eq!(decode_i64(buf.freeze()).unwrap().0, value);
+        }
+    }
+
+    #[test]
+    fn should_reject_lengths_past_u32() {
+        assert_eq!(checked_u32(u32::MAX as usize, "length").unwrap(), u32::MAX);
+        assert!(matches!(
+            checked_u32(u32::MAX as usize + 1, "length"),
+            Err(ImprintError::Overflow {
+                what: "length",
+                value: 4_294_967_296
+            })
+        ));
+    }
+
+    #[test]
+    fn should_roundtrip_zigzag_values() {
+        // Given signed values with known zigzag encodings
+        let cases = [
+            (0i32, 0u32),
+            (-1, 1),
+            (1, 2),
+            (-2, 3),
+            (i32::MAX, u32::MAX - 1),
+            (i32::MIN, u32::MAX),
+        ];
+
+        for (value, expected) in cases {
+            // When zigzag encoding and decoding the value
+            // Then the encoding and roundtrip should be correct
+            assert_eq!(
+                zigzag_encode(value),
+                expected,
+                "Encoding failed for {}",
+                value
+            );
+            assert_eq!(
+                zigzag_decode(expected),
+                value,
+                "Decoding failed for {}",
+                value
+            );
+        }
+    }
 }
@@ -1,16 +1,36 @@
 use bytes::BytesMut;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::{
+    compression::Compression,
     error::ImprintError,
-    serde::Write,
-    types::{DirectoryEntry, Flags, Header, ImprintRecord, SchemaId, Value},
+    types::{DirectoryEntry, Flags, Header, ImprintRecord, Payload, SchemaId, TypeCode, Value},
+    varint,
 };
 
+/// How 32 and 64-bit integer fields are encoded in the payload. Readers see
+/// the same logical integer type whichever encoding was used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegerEncoding {
+    /// Fixed-width little-endian
+    #[default]
+    Fixed,
+    /// Zigzag varints for signed and LEB128 varints for unsigned integers
+    Varint,
+    /// Whichever of the two is smaller for each value
+    Auto,
+}
+
 /// A writer for constructing ImprintRecords by adding fields sequentially.
 pub struct ImprintWriter {
     schema_id: SchemaId,
     fields: BTreeMap<u32, Value>, // keep fields in sorted order
+    compression: Compression,
+    checksum: bool,
+    field_directory: bool,
+    compact_directory: bool,
+    integer_encoding: IntegerEncoding,
+    field_integer_encodings: HashMap<u32, IntegerEncoding>,
 }
 
 impl ImprintWriter {
@@ -19,9 +39,47 @@ impl ImprintWriter {
         Ok(Self {
             schema_id,
             fields: BTreeMap::new(),
+            compression: Compression::None,
+            checksum: false,
+            field_directory: true,
+            compact_directory: false,
+            integer_encoding: IntegerEncoding::Fixed,
+            field_integer_encodings: HashMap::new(),
         })
     }
 
+    /// Sets the codec used to compress the payload when the record is written.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Sets whether a checksum is appended when the record is written.
+    pub fn set_checksum(&mut self, enabled: bool) {
+        self.checksum = enabled;
+    }
+
+    /// Sets whether the field directory is written with the record. Records
+    /// without a directory must be read through a
+    /// [`SchemaRegistry`](crate::SchemaRegistry) that knows their schema.
+    pub fn set_field_directory(&mut self, enabled: bool) {
+        self.field_directory = enabled;
+    }
+
+    /// Sets whether the field directory is written in the compact varint encoding.
+    pub fn set_compact_directory(&mut self, enabled: bool) {
+        self.compact_directory = enabled;
+    }
+
+    /// Sets how integer fields are encoded unless overridden for the field.
+    pub fn set_integer_encoding(&mut self, encoding: IntegerEncoding) {
+        self.integer_encoding = encoding;
+    }
+
+    /// Sets how the integer field with the given id is encoded.
+    pub fn set_field_integer_encoding(&mut self, id: u32, encoding: IntegerEncoding) {
+        self.field_integer_encodings.insert(id, encoding);
+    }
+
     /// Adds a field to the record being built.
     pub fn add_field(&mut self, id: u32, value: Value) -> Result<(), ImprintError> {
         self.fields.insert(id, value);
@@ -34,24 +92,133 @@ impl ImprintWriter {
         let mut payload = BytesMut::new();
 
         for (&id, value) in &self.fields {
+            let offset = varint::checked_u32(payload.len(), "field offset")?;
+            let encoding = self
+                .field_integer_encodings
+                .get(&id)
+                .copied()
+                .unwrap_or(self.integer_encoding);
+            let type_code = write_field(value, encoding, &mut payload)?;
             directory.push(DirectoryEntry {
                 id,
-                type_code: value.type_code(),
-                offset: payload.len() as u32,
+                type_code,
+                offset,
+                length: varint::checked_u32(payload.len(), "payload size")? - offset,
             });
-            value.write(&mut payload)?;
         }
 
+        let flags = Flags::new(0)
+            .with(Flags::FIELD_DIRECTORY, self.field_directory)
+            .with(Flags::COMPRESSED, self.compression != Compression::None)
+            .with(Flags::CHECKSUM, self.checksum)
+            .with(Flags::COMPACT_DIRECTORY, self.compact_directory);
         let header = Header {
-            flags: Flags::new(Flags::FIELD_DIRECTORY),
+            flags,
             schema_id: self.schema_id,
-            payload_size: payload.len() as u32,
+            payload_size: varint::checked_u32(payload.len(), "payload size")?,
         };
 
         Ok(ImprintRecord {
             header,
             directory,
-            payload: payload.freeze(),
+            payload: Payload::new(payload.freeze(), self.compression),
         })
     }
 }
+
+/// Write a field value with the given integer encoding, returning the type code it was written as
+fn write_field(
+    value: &Value,
+    encoding: IntegerEncoding,
+    payload: &mut BytesMut,
+) -> Result<TypeCode, ImprintError> {
+    let type_code = value.type_code();
+    let varint_type_code = match value.varint_type_code() {
+        Some(varint_type_code) if encoding != IntegerEncoding::Fixed => varint_type_code,
+        _ => {
+            value.write_as(type_code, payload)?;
+            return Ok(type_code);
+        }
+    };
+
+    let start = payload.len();
+    value.write_as(varint_type_code, payload)?;
+    if encoding == IntegerEncoding::Auto
+        && payload.len() - start >= type_code.fixed_width().unwrap()
+    {
+        // the varint saved nothing, so fall back to the fixed-width encoding
+        payload.truncate(start);
+        value.write_as(type_code, payload)?;
+        return Ok(type_code);
+    }
+    Ok(varint_type_code)
+}
+
+/// Build a record in fieldspace 1 from the given fields, writing integers with the
+/// given encoding
+#[cfg(test)]
+pub(crate) fn test_record(encoding: IntegerEncoding, fields: Vec<(u32, Value)>) -> ImprintRecord {
+    let mut writer = ImprintWriter::new(SchemaId {
+        fieldspace_id: 1,
+        schema_hash: 0x1,
+    })
+    .unwrap();
+    writer.set_integer_encoding(encoding);
+    for (id, value) in fields {
+        writer.add_field(id, value).unwrap();
+    }
+    writer.build().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA_ID: SchemaId = SchemaId {
+        fieldspace_id: 1,
+        schema_hash: 0xdeadbeef,
+    };
+
+    #[test]
+    fn should_encode_integers_by_policy() {
+        // Given a writer that writes varints by default, except for one fixed field
+        let mut writer = ImprintWriter::new(SCHEMA_ID).unwrap();
+        writer.set_integer_encoding(IntegerEncoding::Varint);
+        writer.set_field_integer_encoding(2, IntegerEncoding::Fixed);
+        writer.set_field_integer_encoding(4, IntegerEncoding::Auto);
+        writer.set_field_integer_encoding(5, IntegerEncoding::Auto);
+        writer.add_field(1, (-3i64).into()).unwrap();
+        writer.add_field(2, 7i32.into()).unwrap();
+        writer.add_field(3, 300u32.into()).unwrap();
+        writer.add_field(4, 1u64.into()).unwrap();
+        writer.add_field(5, i32::MIN.into()).unwrap();
+        writer.add_field(6, "not an integer".into()).unwrap();
+
+        // When building the record
+        let record = writer.build().unwrap();
+
+        // Then each field should use the encoding chosen for it
+        let encodings: Vec<_> = record
+            .directory
+            .iter()
+            .map(|e| (e.type_code, e.length))
+            .collect();
+        assert_eq!(
+            encodings,
+            vec![
+                (TypeCode::VarInt64, 1),
+                (TypeCode::Int32, 4),
+                (TypeCode::VarUInt32, 2),
+                (TypeCode::VarUInt64, 1),
+                (TypeCode::Int32, 4),
+                (TypeCode::String, 15),
+            ]
+        );
+
+        // And readers should see the logical integer types
+        assert_eq!(record.get_value(1).unwrap(), Some(Value::Int64(-3)));
+        assert_eq!(record.get_value(3).unwrap(), Some(Value::UInt32(300)));
+        assert_eq!(record.get_value(4).unwrap(), Some(Value::UInt64(1)));
+        assert_eq!(record.get_value(5).unwrap(), Some(Value::Int32(i32::MIN)));
+    }
+}